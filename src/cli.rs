@@ -1,6 +1,10 @@
 //! CLI module for the renamer tool.
 //! This module handles the parsing of command-line arguments using the `clap` crate.
 //!
+//! Without a subcommand the tool plans and performs the renames described by the
+//! options, as `apply` does. The options are global, so they can also be given after
+//! a subcommand (`renamer plan -c ... -n ...`).
+//!
 //! # Examples
 //!
 //! ```
@@ -16,11 +20,23 @@
 //!     "--depth", "2",
 //! ];
 //! let cli = Cli::parse_from(args);
-//! assert_eq!(cli.directory, std::path::PathBuf::from("/tmp"));
+//! assert_eq!(cli.directory(), std::path::Path::new("/tmp"));
 //! ```
 
-use clap::Parser;
-use std::path::PathBuf;
+use clap::{Args, Parser, Subcommand};
+use std::path::{Path, PathBuf};
+use crate::config::ConfigFormat;
+use crate::diff::ColorChoice;
+use crate::plan::{ConflictPolicy, SortKey};
+use crate::report::OutputFormat;
+use crate::rule::RuleSpec;
+
+/// Directory processed when none is configured.
+pub const DEFAULT_DIRECTORY: &str = ".";
+/// New file name pattern used when none is configured.
+pub const DEFAULT_NEW_PATTERN: &str = "[{title} - ]S{season:02}E{episode:02}";
+/// Depth of recursion used when none is configured.
+pub const DEFAULT_DEPTH: usize = 1;
 
 /// CLI configuration for the Renamer tool.
 ///
@@ -31,42 +47,210 @@ use std::path::PathBuf;
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 pub struct Cli {
-    /// Path to a configuration file (optional). Supports TOML format.
+    /// Optional subcommand. Without one, files are renamed as with `apply`.
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Path to a configuration file (optional). Supports TOML, YAML and JSON formats.
     /// 
     /// **Note:** Values from the configuration file are merged, but CLI options take priority.
-    #[arg(long, help = "Path to a TOML configuration file. CLI options override config file values.")]
+    #[arg(long, global = true, help = "Path to a TOML, YAML or JSON configuration file. CLI options override config file values.")]
     pub config: Option<PathBuf>,
 
-    /// Directory to process (short: -d)
-    #[arg(short, long, default_value = ".")]
-    pub directory: PathBuf,
+    /// Format of the --config file: toml, yaml or json (default: by extension, TOML otherwise)
+    #[arg(long, global = true, value_enum)]
+    pub config_format: Option<ConfigFormat>,
+
+    /// Named profile of the configuration file to use (a `[profile.NAME]` table)
+    #[arg(short, long, global = true)]
+    pub profile: Option<String>,
+
+    /// Do not read `.renamer.toml` files or the user config, only the file given with `--config`
+    #[arg(long, global = true)]
+    pub no_discovery: bool,
+
+    /// Print the effective configuration and where each value comes from, then exit
+    #[arg(long, global = true)]
+    pub show_config: bool,
+
+    /// Directory to process (short: -d, default: ".")
+    #[arg(short, long, global = true)]
+    pub directory: Option<PathBuf>,
+
+    /// Built-in or configured preset supplying the current pattern and a default new
+    /// pattern, e.g. "tv-sxxeyy" (see `renamer presets`)
+    #[arg(long, global = true)]
+    pub preset: Option<String>,
 
     /// Current file regex pattern with named groups 
-    /// (e.g., "S(?P<season>\\d+)E(?P<episode>\\d+)" or if season is absent, a pattern that only captures episode)
-    #[arg(short, long)]
-    pub current_pattern: String,
+    /// (e.g., "S(?P<season>\\d+)E(?P<episode>\\d+)" or if season is absent, a pattern that only captures episode).
+    /// Required to plan or rename files, either here or in the config file.
+    #[arg(short, long, global = true)]
+    pub current_pattern: Option<String>,
+
+    /// New file name pattern (default: "[{title} - ]S{season:02}E{episode:02}", the title
+    /// part being left out when the pattern has no title group)
+    #[arg(short, long, global = true)]
+    pub new_pattern: Option<String>,
 
-    /// New file name pattern (default: "{title} - S{season:02}E{episode:02}")
-    #[arg(short, long, default_value = "{title} - S{season:02}E{episode:02}")]
-    pub new_pattern: String,
+    /// Additional rule "PATTERN=>TEMPLATE", repeatable. Each file is renamed by the first
+    /// matching rule; --current-pattern/--new-pattern, if given, is tried first.
+    #[arg(long = "rule", global = true, value_name = "PATTERN=>TEMPLATE")]
+    pub rules: Vec<RuleSpec>,
 
     /// Comma-separated list of file types/extensions to process (e.g., "mkv,ass,srt")
-    #[arg(short = 't', long, value_delimiter = ',')]
+    #[arg(short = 't', long, global = true, value_delimiter = ',')]
     pub file_types: Vec<String>,
 
     /// Dry run mode: if set, the tool will only print intended changes without renaming files.
-    #[arg(long)]
+    #[arg(long, global = true)]
     pub dry_run: bool,
 
+    /// What to do when a target name is already taken: skip, overwrite, suffix or abort
+    /// (default: abort)
+    #[arg(long, global = true, value_enum)]
+    pub on_conflict: Option<ConflictPolicy>,
+
+    /// All-or-nothing mode: on the first failed rename, roll back every rename already
+    /// performed in this run.
+    #[arg(long, global = true)]
+    pub atomic: bool,
+
+    /// Order of the files in the plan and its output: name, natural, mtime or size
+    /// (default: natural)
+    #[arg(long, global = true, value_enum)]
+    pub sort: Option<SortKey>,
+
+    /// First value of the `{n}` sequence counter (default: 1)
+    #[arg(long, global = true, allow_negative_numbers = true)]
+    pub counter_start: Option<i64>,
+
+    /// Increment of the `{n}` sequence counter between files (default: 1)
+    #[arg(long, global = true, allow_negative_numbers = true)]
+    pub counter_step: Option<i64>,
+
+    /// Order in which files are numbered by `{n}`: name, natural, mtime or size
+    /// (default: the plan order set by `--sort`)
+    #[arg(long, global = true, value_enum)]
+    pub counter_sort: Option<SortKey>,
+
+    /// Editor mode: review and touch up the proposed new names in `$VISUAL`/`$EDITOR`
+    /// before they are validated. Without --current-pattern, every file keeps its name
+    /// until edited.
+    #[arg(long, global = true)]
+    pub edit: bool,
+
+    /// Interactive mode: review the plan file by file, accepting, skipping or editing
+    /// each rename, or accepting all remaining ones.
+    #[arg(short, long, global = true)]
+    pub interactive: bool,
+
     /// Depth of recursion for renaming files (default: 1)
-    #[arg(long, default_value_t = 1)]
-    pub depth: usize,
+    #[arg(long, global = true)]
+    pub depth: Option<usize>,
+
+    /// Print the plan (dry run) or the outcome of the run as json, csv, tsv or table
+    /// instead of log lines; log messages then go to stderr
+    #[arg(long, global = true, value_enum)]
+    pub format: Option<OutputFormat>,
+
+    /// Highlight the changed parts of each name in dry-run output: auto, always or never
+    /// (default: auto, i.e. only on a terminal and when NO_COLOR is unset)
+    #[arg(long, global = true, value_enum)]
+    pub color: Option<ColorChoice>,
+
+    /// Write the computed rename plan, with warnings and conflicts, to this JSON file.
+    /// Implies --dry-run: the plan is applied later with `renamer apply --plan`.
+    #[arg(long, global = true)]
+    pub plan_out: Option<PathBuf>,
+
+    /// Path to the undo journal (default: "$XDG_DATA_HOME/renamer/journal.jsonl")
+    #[arg(long, global = true)]
+    pub journal: Option<PathBuf>,
+}
+
+impl Cli {
+    /// Directory to process, [`DEFAULT_DIRECTORY`] if none is configured.
+    pub fn directory(&self) -> &Path {
+        self.directory.as_deref().unwrap_or(Path::new(DEFAULT_DIRECTORY))
+    }
+
+    /// New file name pattern, [`DEFAULT_NEW_PATTERN`] if none is configured.
+    pub fn new_pattern(&self) -> &str {
+        self.new_pattern.as_deref().unwrap_or(DEFAULT_NEW_PATTERN)
+    }
+
+    /// Depth of recursion, [`DEFAULT_DEPTH`] if none is configured.
+    pub fn depth(&self) -> usize {
+        self.depth.unwrap_or(DEFAULT_DEPTH)
+    }
+
+    /// Returns the rules to try, in order: --current-pattern with --new-pattern if a
+    /// pattern is given, then the --rule options.
+    pub fn rule_specs(&self) -> Vec<RuleSpec> {
+        let primary = self.current_pattern.as_ref().map(|pattern| RuleSpec {
+            name: None,
+            pattern: pattern.clone(),
+            template: self.new_pattern().to_string(),
+        });
+        primary.into_iter().chain(self.rules.iter().cloned()).collect()
+    }
+}
+
+/// Subcommands of the Renamer tool.
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Compute and print the rename plan without touching any file.
+    Plan,
+    /// Compute the rename plan and perform it (the default without a subcommand).
+    Apply(ApplyArgs),
+    /// Revert a previous run recorded in the journal (`--dry-run` only prints the reverts).
+    Undo(UndoArgs),
+    /// Try the pattern and template against sample file names without touching the disk.
+    Test(TestArgs),
+    /// Validate the configuration (config file, pattern, template and directory).
+    Check,
+    /// List the presets available to `--preset`, with their pattern and template.
+    Presets,
+}
+
+/// Arguments of the `apply` subcommand.
+#[derive(Args, Debug)]
+pub struct ApplyArgs {
+    /// Perform the renames of a plan saved with `--plan-out` instead of computing one.
+    /// Refused if a source file changed since the plan was made.
+    #[arg(long)]
+    pub plan: Option<PathBuf>,
+}
+
+/// Arguments of the `test` subcommand.
+#[derive(Args, Debug)]
+pub struct TestArgs {
+    /// Sample file names to rename, e.g. "Show_S1E2.mkv"
+    #[arg(required = true)]
+    pub names: Vec<String>,
+}
+
+/// Arguments of the `undo` subcommand.
+#[derive(Args, Debug)]
+pub struct UndoArgs {
+    /// Id of the run to revert (default: the most recent run not yet undone)
+    #[arg(long)]
+    pub run: Option<String>,
+
+    /// List the runs recorded in the journal instead of undoing one.
+    #[arg(long)]
+    pub list: bool,
+
+    /// Revert the files that are unchanged and skip those moved or modified since the run.
+    #[arg(long)]
+    pub force: bool,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::path::PathBuf;
+    use std::path::{Path, PathBuf};
 
     #[test]
     fn test_cli_parsing() {
@@ -76,16 +260,37 @@ mod tests {
             "-c", r"S(?P<season>\d+)E(?P<episode>\d+)",
             "-n", "{title} - S{season:02}E{episode:02}",
             "-t", "mkv,ass",
+            "--rule", r"(?P<season>\d+)x(?P<episode>\d+)=>S{season:02}E{episode:02}",
             "--dry-run",
             "--depth", "3",
+            "--on-conflict", "suffix",
+            "--sort", "name",
+            "--counter-start", "0",
+            "--counter-step", "-2",
+            "--counter-sort", "mtime",
+            "--format", "tsv",
+            "--edit",
+            "-i",
+            "--color", "never",
         ];
         let cli = Cli::parse_from(args);
-        assert_eq!(cli.directory, PathBuf::from("/path/to/dir"));
-        assert_eq!(cli.current_pattern, r"S(?P<season>\d+)E(?P<episode>\d+)");
-        assert_eq!(cli.new_pattern, "{title} - S{season:02}E{episode:02}");
+        assert_eq!(cli.directory(), Path::new("/path/to/dir"));
+        assert_eq!(cli.current_pattern.as_deref(), Some(r"S(?P<season>\d+)E(?P<episode>\d+)"));
+        assert_eq!(cli.new_pattern(), "{title} - S{season:02}E{episode:02}");
         assert_eq!(cli.file_types, vec!["mkv".to_string(), "ass".to_string()]);
+        assert_eq!(cli.rules.len(), 1);
+        assert_eq!(cli.rules[0].pattern, r"(?P<season>\d+)x(?P<episode>\d+)");
         assert!(cli.dry_run);
-        assert_eq!(cli.depth, 3);
+        assert_eq!(cli.depth(), 3);
+        assert_eq!(cli.on_conflict, Some(ConflictPolicy::Suffix));
+        assert_eq!(cli.sort, Some(SortKey::Name));
+        assert_eq!(cli.counter_start, Some(0));
+        assert_eq!(cli.counter_step, Some(-2));
+        assert_eq!(cli.counter_sort, Some(SortKey::Mtime));
+        assert_eq!(cli.format, Some(OutputFormat::Tsv));
+        assert!(cli.edit);
+        assert!(cli.interactive);
+        assert_eq!(cli.color, Some(ColorChoice::Never));
     }
 
     #[test]
@@ -99,6 +304,49 @@ mod tests {
             "--depth", "3",
         ];
         let cli = Cli::parse_from(args);
-        assert_eq!(cli.directory, PathBuf::from("."));
+        assert_eq!(cli.directory, None);
+        assert_eq!(cli.directory(), Path::new("."));
+    }
+
+    #[test]
+    fn test_cli_undo_without_pattern() {
+        let args = vec!["renamer", "undo", "--run", "20250317-120000-000000", "--journal", "/tmp/j.jsonl", "--dry-run"];
+        let cli = Cli::parse_from(args);
+        assert_eq!(cli.journal, Some(PathBuf::from("/tmp/j.jsonl")));
+        assert_eq!(cli.current_pattern, None);
+        assert!(cli.dry_run);
+        match cli.command {
+            Some(Command::Undo(undo)) => {
+                assert_eq!(undo.run.as_deref(), Some("20250317-120000-000000"));
+                assert!(!undo.list);
+            }
+            other => panic!("expected undo subcommand, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_cli_subcommands_accept_global_options() {
+        let cli = Cli::parse_from(vec!["renamer", "plan", "-c", r"(?P<title>.+)", "--depth", "2"]);
+        assert!(matches!(cli.command, Some(Command::Plan)));
+        assert_eq!(cli.current_pattern.as_deref(), Some("(?P<title>.+)"));
+        assert_eq!(cli.depth(), 2);
+
+        let cli = Cli::parse_from(vec!["renamer", "-c", "(?P<title>.+)", "test", "-n", "{title|upper}", "a.mkv", "b.mkv"]);
+        match &cli.command {
+            Some(Command::Test(test)) => assert_eq!(test.names, vec!["a.mkv", "b.mkv"]),
+            other => panic!("expected test subcommand, got {:?}", other),
+        }
+        assert_eq!(cli.new_pattern(), "{title|upper}");
+
+        let cli = Cli::parse_from(vec!["renamer", "apply", "--plan", "plan.json"]);
+        match cli.command {
+            Some(Command::Apply(apply)) => assert_eq!(apply.plan, Some(PathBuf::from("plan.json"))),
+            other => panic!("expected apply subcommand, got {:?}", other),
+        }
+        assert_eq!(cli.current_pattern, None);
+
+        let cli = Cli::parse_from(vec!["renamer", "check", "--config", "renamer.toml"]);
+        assert!(matches!(cli.command, Some(Command::Check)));
+        assert_eq!(cli.config, Some(PathBuf::from("renamer.toml")));
     }
 }
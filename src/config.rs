@@ -1,65 +1,699 @@
+use std::collections::BTreeMap;
+use std::fmt;
 use std::fs;
+use std::path::{Component, Path, PathBuf};
 use anyhow::{anyhow, Result};
-use serde::Deserialize;
-use crate::cli::Cli;
+use clap::ValueEnum;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use crate::cli::{Cli, DEFAULT_DEPTH, DEFAULT_DIRECTORY, DEFAULT_NEW_PATTERN};
+use crate::file_ops::absolute;
+use crate::plan::{ConflictPolicy, Sequence, SortKey};
+use crate::preset;
+use crate::report::OutputFormat;
+use crate::rule::{Rule, RuleSpec};
+use crate::template::Template;
 
-#[derive(Deserialize, Debug)]
+/// Name of the per-directory config file discovered in the target directory and its ancestors.
+pub const PROJECT_CONFIG: &str = ".renamer.toml";
+
+/// Keys of [`AppConfig`] that hold settings, in the order `--show-config` lists them.
+pub const KEYS: [&str; 15] = [
+    "directory",
+    "preset",
+    "current_pattern",
+    "new_pattern",
+    "file_types",
+    "rules",
+    "dry_run",
+    "atomic",
+    "on_conflict",
+    "sort",
+    "counter_start",
+    "counter_step",
+    "counter_sort",
+    "format",
+    "depth",
+];
+
+/// One layer of settings, as read from a config file or taken from the command line.
+/// A value that is `None` is not set by the layer.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
 pub struct AppConfig {
-    pub directory: Option<String>,
+    pub directory: Option<PathBuf>,
+    /// Preset supplying the current pattern and new pattern that are not set otherwise.
+    pub preset: Option<String>,
     pub current_pattern: Option<String>,
     pub new_pattern: Option<String>,
     pub file_types: Option<Vec<String>>,
+    pub rules: Option<Vec<RuleSpec>>,
     pub dry_run: Option<bool>,
-    // Removed default_season and title fields
+    pub atomic: Option<bool>,
+    pub on_conflict: Option<ConflictPolicy>,
+    pub sort: Option<SortKey>,
+    pub counter_start: Option<i64>,
+    pub counter_step: Option<i64>,
+    pub counter_sort: Option<SortKey>,
+    pub format: Option<OutputFormat>,
     pub depth: Option<usize>,
+    /// Named profiles (`[profile.NAME]` tables) selected with `--profile`; a profile's
+    /// values take the place of the top-level ones.
+    #[serde(skip_serializing)]
+    pub profile: Option<BTreeMap<String, AppConfig>>,
+    /// Presets defined in this layer (`[presets.NAME]` tables with a `pattern` and a
+    /// `template`), overriding built-in presets of the same name.
+    #[serde(skip_serializing)]
+    pub presets: Option<BTreeMap<String, RuleSpec>>,
+}
+
+impl AppConfig {
+    /// The built-in defaults, the lowest layer of the settings.
+    pub fn defaults() -> AppConfig {
+        let sequence = Sequence::default();
+        AppConfig {
+            directory: Some(DEFAULT_DIRECTORY.into()),
+            new_pattern: Some(DEFAULT_NEW_PATTERN.to_string()),
+            file_types: Some(Vec::new()),
+            rules: Some(Vec::new()),
+            dry_run: Some(false),
+            atomic: Some(false),
+            on_conflict: Some(ConflictPolicy::default()),
+            sort: Some(SortKey::default()),
+            counter_start: Some(sequence.start),
+            counter_step: Some(sequence.step),
+            depth: Some(DEFAULT_DEPTH),
+            ..AppConfig::default()
+        }
+    }
+
+    /// The settings given on the command line. Flags that are not given and lists
+    /// that are empty are not set.
+    pub fn from_cli(cli: &Cli) -> AppConfig {
+        AppConfig {
+            directory: cli.directory.clone(),
+            preset: cli.preset.clone(),
+            current_pattern: cli.current_pattern.clone(),
+            new_pattern: cli.new_pattern.clone(),
+            file_types: Some(cli.file_types.clone()).filter(|v| !v.is_empty()),
+            rules: Some(cli.rules.clone()).filter(|v| !v.is_empty()),
+            dry_run: cli.dry_run.then_some(true),
+            atomic: cli.atomic.then_some(true),
+            on_conflict: cli.on_conflict,
+            sort: cli.sort,
+            counter_start: cli.counter_start,
+            counter_step: cli.counter_step,
+            counter_sort: cli.counter_sort,
+            format: cli.format,
+            depth: cli.depth,
+            profile: None,
+            presets: None,
+        }
+    }
+
+    /// Returns the keys of the values set in this layer.
+    pub fn keys(&self) -> Vec<&'static str> {
+        let set = [
+            self.directory.is_some(),
+            self.preset.is_some(),
+            self.current_pattern.is_some(),
+            self.new_pattern.is_some(),
+            self.file_types.is_some(),
+            self.rules.is_some(),
+            self.dry_run.is_some(),
+            self.atomic.is_some(),
+            self.on_conflict.is_some(),
+            self.sort.is_some(),
+            self.counter_start.is_some(),
+            self.counter_step.is_some(),
+            self.counter_sort.is_some(),
+            self.format.is_some(),
+            self.depth.is_some(),
+        ];
+        KEYS.into_iter().zip(set).filter_map(|(key, set)| set.then_some(key)).collect()
+    }
+
+    /// Checks the patterns and templates set in this layer, its presets and its profiles: regexes
+    /// must compile, templates must parse, and a template must only use groups of the
+    /// pattern it is paired with in the same table.
+    ///
+    /// # Errors
+    ///
+    /// Returns a message naming the offending key.
+    pub fn validate(&self) -> Result<(), String> {
+        let pattern = match &self.current_pattern {
+            Some(pattern) => Some(Regex::new(pattern).map_err(|e| format!("current_pattern: invalid regex pattern: {}", e))?),
+            None => None,
+        };
+        if let Some(new_pattern) = &self.new_pattern {
+            let template = Template::parse(new_pattern).map_err(|e| format!("new_pattern: {}", e))?;
+            if let Some(pattern) = &pattern {
+                template.check_groups(pattern).map_err(|e| format!("new_pattern: {}", e))?;
+            }
+        }
+        for (index, spec) in self.rules.iter().flatten().enumerate() {
+            Rule::compile(spec, index).map_err(|e| format!("rules: {}", e))?;
+        }
+        for (name, spec) in self.presets.iter().flatten() {
+            let spec = RuleSpec { name: Some(name.clone()), ..spec.clone() };
+            Rule::compile(&spec, 0).map_err(|e| format!("presets: {}", e))?;
+        }
+        for (name, profile) in self.profile.iter().flatten() {
+            profile.validate().map_err(|e| format!("profile.{}.{}", name, e))?;
+        }
+        Ok(())
+    }
+
+    /// Returns `self` with every value that is set in `upper` replaced by it. Presets
+    /// are merged by name.
+    pub fn overlay(self, upper: AppConfig) -> AppConfig {
+        AppConfig {
+            directory: upper.directory.or(self.directory),
+            preset: upper.preset.or(self.preset),
+            current_pattern: upper.current_pattern.or(self.current_pattern),
+            new_pattern: upper.new_pattern.or(self.new_pattern),
+            file_types: upper.file_types.or(self.file_types),
+            rules: upper.rules.or(self.rules),
+            dry_run: upper.dry_run.or(self.dry_run),
+            atomic: upper.atomic.or(self.atomic),
+            on_conflict: upper.on_conflict.or(self.on_conflict),
+            sort: upper.sort.or(self.sort),
+            counter_start: upper.counter_start.or(self.counter_start),
+            counter_step: upper.counter_step.or(self.counter_step),
+            counter_sort: upper.counter_sort.or(self.counter_sort),
+            format: upper.format.or(self.format),
+            depth: upper.depth.or(self.depth),
+            profile: upper.profile.or(self.profile),
+            presets: match (self.presets, upper.presets) {
+                (Some(mut lower), Some(upper)) => {
+                    lower.extend(upper);
+                    Some(lower)
+                }
+                (lower, upper) => upper.or(lower),
+            },
+        }
+    }
+
+    /// Writes the values into `cli`, replacing what was parsed from the command line.
+    fn apply_to(&self, cli: &mut Cli) {
+        cli.directory = self.directory.clone();
+        cli.preset = self.preset.clone();
+        cli.current_pattern = self.current_pattern.clone();
+        cli.new_pattern = self.new_pattern.clone();
+        cli.file_types = self.file_types.clone().unwrap_or_default();
+        cli.rules = self.rules.clone().unwrap_or_default();
+        cli.dry_run = self.dry_run.unwrap_or_default();
+        cli.atomic = self.atomic.unwrap_or_default();
+        cli.on_conflict = self.on_conflict;
+        cli.sort = self.sort;
+        cli.counter_start = self.counter_start;
+        cli.counter_step = self.counter_step;
+        cli.counter_sort = self.counter_sort;
+        cli.format = self.format;
+        cli.depth = self.depth;
+    }
+}
+
+/// Where a setting comes from, from the lowest precedence to the highest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// The built-in default.
+    Default,
+    /// The preset selected with `--preset` (see [`crate::preset`]).
+    Preset(String),
+    /// The user config file.
+    UserConfig(PathBuf),
+    /// A [`PROJECT_CONFIG`] file of the target directory or one of its ancestors.
+    ProjectConfig(PathBuf),
+    /// The file given with `--config`.
+    ConfigFile(PathBuf),
+    /// The profile selected with `--profile`, and the file defining it.
+    Profile { name: String, path: PathBuf },
+    /// An environment variable (see [`env_var`]).
+    Env(String),
+    /// The command line.
+    Cli,
+}
+
+impl Source {
+    /// The config file the setting was read from, if any.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Source::UserConfig(path) | Source::ProjectConfig(path) | Source::ConfigFile(path) => Some(path),
+            Source::Profile { path, .. } => Some(path),
+            Source::Default | Source::Preset(_) | Source::Env(_) | Source::Cli => None,
+        }
+    }
 }
 
-/// Merges configuration from a TOML file into the provided CLI instance.
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Default => write!(f, "default"),
+            Source::Preset(name) => write!(f, "preset '{}'", name),
+            Source::UserConfig(path) => write!(f, "user config {:?}", path),
+            Source::ProjectConfig(path) => write!(f, "project config {:?}", path),
+            Source::ConfigFile(path) => write!(f, "config file {:?}", path),
+            Source::Profile { name, path } => write!(f, "profile '{}' in {:?}", name, path),
+            Source::Env(var) => write!(f, "environment {}", var),
+            Source::Cli => write!(f, "command line"),
+        }
+    }
+}
+
+/// The effective settings and where each of them comes from.
+#[derive(Debug, Default)]
+pub struct Settings {
+    pub values: AppConfig,
+    sources: BTreeMap<&'static str, Source>,
+}
+
+impl Settings {
+    /// Puts `layer` on top of the settings: every value it sets replaces the current one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use renamer::config::{AppConfig, Settings, Source};
+    /// let mut settings = Settings::default();
+    /// settings.push(Source::Default, AppConfig::defaults());
+    /// settings.push(Source::Cli, AppConfig { depth: Some(1), ..AppConfig::default() });
+    /// assert_eq!(settings.values.depth, Some(1));
+    /// assert_eq!(settings.source("depth"), Some(&Source::Cli));
+    /// assert_eq!(settings.source("sort"), Some(&Source::Default));
+    /// ```
+    pub fn push(&mut self, source: Source, layer: AppConfig) {
+        for key in layer.keys() {
+            self.sources.insert(key, source.clone());
+        }
+        self.values = std::mem::take(&mut self.values).overlay(layer);
+    }
+
+    /// Returns where the value of `key` comes from, or `None` if it is not set.
+    pub fn source(&self, key: &str) -> Option<&Source> {
+        self.sources.get(key)
+    }
+}
+
+impl fmt::Display for Settings {
+    /// Lists every setting as a TOML line commented with its source.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let values = toml::Value::try_from(&self.values).map_err(|_| fmt::Error)?;
+        for key in KEYS {
+            match (values.get(key), self.source(key)) {
+                (Some(value), Some(source)) => writeln!(f, "{} = {}  # {}", key, value, source)?,
+                _ => writeln!(f, "# {} is not set", key)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the environment variable that overrides `key`, e.g. `RENAMER_FILE_TYPES`.
+pub fn env_var(key: &str) -> String {
+    format!("RENAMER_{}", key.to_uppercase())
+}
+
+/// Reads the settings overridden by environment variables, one layer per variable.
 ///
-/// If `cli.config` is set, the configuration file is read and its values
-/// are used to fill in any missing CLI options. **Important:** Options provided
-/// on the command line will always override values from the config file.
+/// `lookup` returns the value of a variable; empty values count as unset. Lists are
+/// written as on the command line: file types separated by commas, and rules as
+/// `PATTERN=>TEMPLATE`, one per line.
 ///
 /// # Errors
 ///
-/// Returns an error if the configuration file cannot be read or parsed.
-pub fn merge_config(cli: &mut Cli) -> Result<(), anyhow::Error> {
-    if let Some(config_path) = cli.config.as_ref() {
-        let config_str = fs::read_to_string(config_path)
-            .map_err(|e| anyhow!("Failed to read config file: {}", e))?;
-        let config: AppConfig = toml::from_str(&config_str)
-            .map_err(|e| anyhow!("Failed to parse config file: {}", e))?;
-        if cli.directory.as_os_str().is_empty() {
-            if let Some(dir) = config.directory {
-                cli.directory = dir.into();
-            }
-        }
-        if cli.current_pattern.is_empty() {
-            if let Some(val) = config.current_pattern {
-                cli.current_pattern = val;
+/// Returns an error naming the variable if its value cannot be parsed.
+///
+/// # Examples
+///
+/// ```
+/// # use renamer::config::{env_layers, Source};
+/// let layers = env_layers(|var| match var {
+///     "RENAMER_FILE_TYPES" => Some("mkv,mp4".to_string()),
+///     "RENAMER_DRY_RUN" => Some("true".to_string()),
+///     _ => None,
+/// }).unwrap();
+/// assert_eq!(layers.len(), 2);
+/// assert_eq!(layers[0].0, Source::Env("RENAMER_FILE_TYPES".to_string()));
+/// assert_eq!(layers[0].1.file_types, Some(vec!["mkv".to_string(), "mp4".to_string()]));
+/// assert_eq!(layers[1].1.dry_run, Some(true));
+/// ```
+pub fn env_layers(lookup: impl Fn(&str) -> Option<String>) -> Result<Vec<(Source, AppConfig)>> {
+    let mut layers = Vec::new();
+    for key in KEYS {
+        let var = env_var(key);
+        let Some(value) = lookup(&var).filter(|v| !v.is_empty()) else {
+            continue;
+        };
+        let layer = env_layer(key, &value).map_err(|e| anyhow!("Invalid value '{}' for {}: {}", value, var, e))?;
+        layers.push((Source::Env(var), layer));
+    }
+    Ok(layers)
+}
+
+/// Parses the value of the environment variable for `key` into a layer setting only `key`.
+fn env_layer(key: &str, value: &str) -> Result<AppConfig, String> {
+    fn number<T: std::str::FromStr<Err = std::num::ParseIntError>>(value: &str) -> Result<T, String> {
+        value.trim().parse().map_err(|e: std::num::ParseIntError| e.to_string())
+    }
+    fn flag(value: &str) -> Result<bool, String> {
+        value.trim().parse().map_err(|_| "expected true or false".to_string())
+    }
+    let mut layer = AppConfig::default();
+    match key {
+        "directory" => layer.directory = Some(value.into()),
+        "preset" => layer.preset = Some(value.trim().to_string()),
+        "current_pattern" => layer.current_pattern = Some(value.to_string()),
+        "new_pattern" => layer.new_pattern = Some(value.to_string()),
+        "file_types" => layer.file_types = Some(value.split(',').map(|t| t.trim().to_string()).filter(|t| !t.is_empty()).collect()),
+        "rules" => layer.rules = Some(value.lines().filter(|l| !l.trim().is_empty()).map(str::parse).collect::<Result<_, _>>()?),
+        "dry_run" => layer.dry_run = Some(flag(value)?),
+        "atomic" => layer.atomic = Some(flag(value)?),
+        "on_conflict" => layer.on_conflict = Some(ConflictPolicy::from_str(value.trim(), true)?),
+        "sort" => layer.sort = Some(SortKey::from_str(value.trim(), true)?),
+        "counter_start" => layer.counter_start = Some(number(value)?),
+        "counter_step" => layer.counter_step = Some(number(value)?),
+        "counter_sort" => layer.counter_sort = Some(SortKey::from_str(value.trim(), true)?),
+        "format" => layer.format = Some(OutputFormat::from_str(value.trim(), true)?),
+        "depth" => layer.depth = Some(number(value)?),
+        _ => return Err(format!("unknown setting {}", key)),
+    }
+    Ok(layer)
+}
+
+/// Returns the path of the user config file, `$XDG_CONFIG_HOME/renamer/config.toml`,
+/// falling back to `$HOME/.config/renamer/config.toml` (or `%APPDATA%` on Windows).
+pub fn user_config_path() -> Option<PathBuf> {
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))
+        .or_else(|| std::env::var_os("HOME").map(|h| Path::new(&h).join(".config")))?;
+    Some(config_dir.join("renamer").join("config.toml"))
+}
+
+/// Finds the config files that apply to `directory`, lowest precedence first: the
+/// user config (if it exists), then every [`PROJECT_CONFIG`] from the file system root
+/// down to `directory` itself.
+pub fn discover_configs(directory: &Path, user_config: Option<&Path>) -> Vec<PathBuf> {
+    let mut found: Vec<PathBuf> = user_config.filter(|p| p.is_file()).map(Path::to_path_buf).into_iter().collect();
+    let directory = if directory.as_os_str().is_empty() { Path::new(".") } else { directory };
+    // Resolve `..` first, so `cwd/../target` does not pick up the config of `cwd`.
+    let directory = fs::canonicalize(directory).unwrap_or_else(|_| {
+        absolute(directory).components().fold(PathBuf::new(), |mut path, component| {
+            match component {
+                Component::ParentDir => {
+                    path.pop();
+                }
+                Component::CurDir => {}
+                component => path.push(component),
             }
+            path
+        })
+    });
+    let mut project: Vec<PathBuf> = directory
+        .ancestors()
+        .map(|dir| dir.join(PROJECT_CONFIG))
+        .filter(|path| path.is_file())
+        .collect();
+    project.reverse();
+    found.extend(project);
+    found
+}
+
+/// Formats of config files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum ConfigFormat {
+    #[default]
+    Toml,
+    Yaml,
+    Json,
+}
+
+/// A config file that could not be parsed, with the 1-based line and column of the error.
+struct ParseError {
+    message: String,
+    location: Option<(usize, usize)>,
+}
+
+impl ConfigFormat {
+    /// Guesses the format from the extension of `path`: `.yaml` and `.yml` are YAML,
+    /// `.json` is JSON, anything else is TOML.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::path::Path;
+    /// # use renamer::config::ConfigFormat;
+    /// assert_eq!(ConfigFormat::from_path(Path::new("renamer.yml")), ConfigFormat::Yaml);
+    /// assert_eq!(ConfigFormat::from_path(Path::new(".renamer.toml")), ConfigFormat::Toml);
+    /// ```
+    pub fn from_path(path: &Path) -> ConfigFormat {
+        match path.extension().and_then(|e| e.to_str()).map(str::to_lowercase).as_deref() {
+            Some("yaml" | "yml") => ConfigFormat::Yaml,
+            Some("json") => ConfigFormat::Json,
+            _ => ConfigFormat::Toml,
         }
-        if cli.new_pattern.is_empty() {
-            if let Some(val) = config.new_pattern {
-                cli.new_pattern = val;
-            }
+    }
+
+    fn parse(self, text: &str) -> Result<AppConfig, ParseError> {
+        match self {
+            ConfigFormat::Toml => toml::from_str(text).map_err(|e| ParseError {
+                message: e.message().to_string(),
+                location: e.span().map(|span| line_column(text, span.start)),
+            }),
+            // An empty YAML document is null rather than an empty mapping.
+            ConfigFormat::Yaml if text.trim().is_empty() => Ok(AppConfig::default()),
+            ConfigFormat::Yaml => serde_norway::from_str(text).map_err(|e| {
+                let location = e.location().map(|l| (l.line(), l.column()));
+                ParseError { message: strip_location(e.to_string(), location), location }
+            }),
+            ConfigFormat::Json => serde_json::from_str(text).map_err(|e| {
+                let location = Some((e.line(), e.column())).filter(|&(line, _)| line > 0);
+                ParseError { message: strip_location(e.to_string(), location), location }
+            }),
         }
-        if cli.file_types.is_empty() {
-            if let Some(val) = config.file_types {
-                cli.file_types = val;
-            }
+    }
+}
+
+/// Removes the " at line L column C" that serde_json and serde_norway append to their messages.
+fn strip_location(message: String, location: Option<(usize, usize)>) -> String {
+    let Some((line, column)) = location else {
+        return message;
+    };
+    let suffix = format!(" at line {} column {}", line, column);
+    message.strip_suffix(&suffix).map(str::to_string).unwrap_or(message)
+}
+
+/// Reads, parses and validates the config file at `path`, in `format` or, if `None`,
+/// the format given by its extension (see [`ConfigFormat::from_path`]).
+///
+/// # Errors
+///
+/// Returns an error naming the file if it cannot be read, if it cannot be parsed, has
+/// unknown keys or values of the wrong type (with the line and column), or if one of
+/// its patterns or templates is invalid (see [`AppConfig::validate`]).
+pub fn load_config(path: &Path, format: Option<ConfigFormat>) -> Result<AppConfig> {
+    let config_str = fs::read_to_string(path)
+        .map_err(|e| anyhow!("Failed to read config file {:?}: {}", path, e))?;
+    let format = format.unwrap_or_else(|| ConfigFormat::from_path(path));
+    let config = format.parse(&config_str).map_err(|e| match e.location {
+        Some((line, column)) => {
+            anyhow!("Failed to parse config file {:?} at line {}, column {}: {}", path, line, column, e.message)
         }
-        if cli.dry_run {
-            if let Some(val) = config.dry_run {
-                cli.dry_run = val;
-            }
+        None => anyhow!("Failed to parse config file {:?}: {}", path, e.message),
+    })?;
+    config.validate().map_err(|e| anyhow!("Invalid config file {:?}: {}", path, e))?;
+    Ok(config)
+}
+
+/// Returns the 1-based line and column (in characters) of byte `offset` in `text`.
+fn line_column(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (before.matches('\n').count() + 1, before[line_start..].chars().count() + 1)
+}
+
+/// Takes the profile called `name` out of the config `layers`. When several layers
+/// define it, the one read last is used.
+fn take_profile(layers: &mut [(Source, AppConfig)], name: &str) -> Result<(Source, AppConfig)> {
+    if layers.is_empty() {
+        return Err(anyhow!("Profile '{}' was selected but no config file was found (use --config)", name));
+    }
+    let found = layers.iter_mut().rev().find_map(|(source, layer)| {
+        let profile = layer.profile.as_mut()?.remove(name)?;
+        Some((source.path()?.to_path_buf(), profile))
+    });
+    let Some((path, profile)) = found else {
+        let known: std::collections::BTreeSet<&str> = layers
+            .iter()
+            .filter_map(|(_, layer)| layer.profile.as_ref())
+            .flat_map(|profiles| profiles.keys().map(String::as_str))
+            .collect();
+        return Err(if known.is_empty() {
+            anyhow!("Unknown profile '{}': the config files define no profiles", name)
+        } else {
+            anyhow!("Unknown profile '{}': the config files define {}", name, known.into_iter().collect::<Vec<_>>().join(", "))
+        });
+    };
+    if profile.profile.is_some() {
+        return Err(anyhow!("Profile '{}' cannot define profiles of its own", name));
+    }
+    Ok((Source::Profile { name: name.to_string(), path }, profile))
+}
+
+/// Merges the settings of every layer into the provided CLI instance, from the lowest
+/// precedence to the highest:
+///
+/// 1. the built-in defaults;
+/// 2. unless `cli.no_discovery` is set, the user config and the [`PROJECT_CONFIG`] files
+///    of the target directory and its ancestors (see [`discover_configs`]), the target
+///    directory being the one set on the command line, by the environment or by the
+///    `--config` file;
+/// 3. the file given with `--config`, in the format given with `--config-format` or
+///    by its extension;
+/// 4. the profile selected with `--profile`;
+/// 5. the `RENAMER_*` environment variables (see [`env_layers`]);
+/// 6. the options given on the command line.
+///
+/// The pattern and template of the selected preset form a layer of their own, just
+/// below the layer that selects the preset.
+///
+/// A value set in a layer replaces the values of the layers below it, so an option
+/// given on the command line always wins, even if it equals the default. Returns the
+/// effective settings with the source of each value.
+///
+/// # Errors
+///
+/// Returns an error if a configuration file or environment variable cannot be read,
+/// parsed or validated, if the selected profile does not exist, if a profile is
+/// selected without a config file, or if one of the effective rules does not compile.
+pub fn merge_config(cli: &mut Cli) -> Result<Settings> {
+    merge_config_with(cli, |var| std::env::var(var).ok(), user_config_path().as_deref())
+}
+
+/// Like [`merge_config`], but reads environment variables through `env` and the user
+/// config from `user_config` instead of the process environment and
+/// [`user_config_path`], so the result does not depend on the calling environment.
+///
+/// # Errors
+///
+/// Returns the same errors as [`merge_config`].
+///
+/// # Examples
+///
+/// ```
+/// # use clap::Parser;
+/// # use renamer::Cli;
+/// # use renamer::config::{merge_config_with, Source};
+/// let mut cli = Cli::parse_from(["renamer", "--no-discovery"]);
+/// let env = |var: &str| (var == "RENAMER_DEPTH").then(|| "3".to_string());
+/// let settings = merge_config_with(&mut cli, env, None).unwrap();
+/// assert_eq!(cli.depth(), 3);
+/// assert_eq!(settings.source("depth"), Some(&Source::Env("RENAMER_DEPTH".to_string())));
+/// ```
+pub fn merge_config_with(cli: &mut Cli, env: impl Fn(&str) -> Option<String>, user_config: Option<&Path>) -> Result<Settings> {
+    let config = match &cli.config {
+        Some(path) => Some((Source::ConfigFile(path.clone()), load_config(path, cli.config_format)?)),
+        None => None,
+    };
+    let env = env_layers(env)?;
+    let mut layers = Vec::new();
+    if !cli.no_discovery {
+        let directory = discovery_directory(cli, config.as_ref().map(|(_, layer)| layer), &env);
+        for path in discover_configs(&directory, user_config) {
+            let layer = load_config(&path, Some(ConfigFormat::Toml))?;
+            let source = if user_config == Some(path.as_path()) {
+                Source::UserConfig(path)
+            } else {
+                Source::ProjectConfig(path)
+            };
+            layers.push((source, layer));
         }
-        // Removed code blocks for default_season and title
-        if cli.depth == 1 {
-            if let Some(val) = config.depth {
-                cli.depth = val;
+    }
+    layers.extend(config);
+    let profile = match &cli.profile {
+        Some(name) => Some(take_profile(&mut layers, name)?),
+        None => None,
+    };
+
+    let mut layers: Vec<(Source, AppConfig)> = layers
+        .into_iter()
+        .chain(profile)
+        .chain(env)
+        .collect();
+    layers.push((Source::Cli, AppConfig::from_cli(cli)));
+
+    // The preset's pattern and template sit just below the layer that selected it, so
+    // they override the layers beneath it but not the ones above.
+    if let Some((index, preset)) = preset_layer(&layers)? {
+        layers.insert(index, preset);
+    }
+    let mut settings = Settings::default();
+    settings.push(Source::Default, AppConfig::defaults());
+    for (source, mut layer) in layers {
+        layer.profile = None;
+        settings.push(source, layer);
+    }
+    settings.values.apply_to(cli);
+    check_rules(cli, &settings)?;
+    Ok(settings)
+}
+
+/// Returns the directory whose config files are discovered: the one given on the command
+/// line, in an environment variable, or in the `--config` file or its selected profile.
+/// Directories set in discovered files are not followed, as they depend on the discovery.
+fn discovery_directory(cli: &Cli, config: Option<&AppConfig>, env: &[(Source, AppConfig)]) -> PathBuf {
+    let profile = config
+        .zip(cli.profile.as_ref())
+        .and_then(|(layer, name)| layer.profile.as_ref()?.get(name)?.directory.clone());
+    cli.directory
+        .clone()
+        .or_else(|| env.iter().rev().find_map(|(_, layer)| layer.directory.clone()))
+        .or(profile)
+        .or_else(|| config?.directory.clone())
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_DIRECTORY))
+}
+
+/// Returns the pattern and template of the preset selected in the highest of `layers`
+/// that selects one, with the index of that layer, looking the preset up in the presets
+/// defined by the layers first.
+fn preset_layer(layers: &[(Source, AppConfig)]) -> Result<Option<(usize, (Source, AppConfig))>> {
+    let selected = layers.iter().enumerate().rev().find_map(|(i, (_, layer))| Some((i, layer.preset.as_deref()?)));
+    let Some((index, name)) = selected else {
+        return Ok(None);
+    };
+    let mut custom = BTreeMap::new();
+    for (_, layer) in layers {
+        custom.extend(layer.presets.clone().unwrap_or_default());
+    }
+    let spec = preset::resolve(name, &custom).ok_or_else(|| {
+        anyhow!("Unknown preset '{}': choose one of {}", name, preset::names(&custom).join(", "))
+    })?;
+    let layer = AppConfig {
+        current_pattern: Some(spec.pattern),
+        new_pattern: Some(spec.template),
+        ..AppConfig::default()
+    };
+    Ok(Some((index, (Source::Preset(name.to_string()), layer))))
+}
+
+/// Compiles the effective rules, whose pattern and template may come from different
+/// layers, so a bad combination is reported before any file is looked at.
+fn check_rules(cli: &Cli, settings: &Settings) -> Result<()> {
+    let origin = |key: &str| match settings.source(key) {
+        Some(source) => format!("{} from {}", key, source),
+        None => key.to_string(),
+    };
+    for (index, spec) in cli.rule_specs().iter().enumerate() {
+        let primary = cli.current_pattern.is_some() && index == 0;
+        Rule::compile(spec, index).map_err(|e| {
+            if primary {
+                anyhow!("{} ({}, {})", e, origin("current_pattern"), origin("new_pattern"))
+            } else {
+                anyhow!("{} ({})", e, origin("rules"))
             }
-        }
+        })?;
     }
     Ok(())
 }
@@ -0,0 +1,179 @@
+//! Diff module for the renamer tool.
+//! This module computes a character diff between an old and a new file name so the
+//! dry-run output can highlight what a rename changes: removed characters in red in
+//! the old name, added characters in green in the new one.
+
+use std::env;
+use std::path::Path;
+use clap::ValueEnum;
+use crate::file_ops::file_name;
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const RESET: &str = "\x1b[0m";
+
+/// When to color the output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum ColorChoice {
+    /// Color when writing to a terminal and `NO_COLOR` is not set.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Decides whether to color output going to a terminal (`is_tty`) or not.
+    pub fn enabled(self, is_tty: bool) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => is_tty && env::var_os("NO_COLOR").is_none_or(|v| v.is_empty()),
+        }
+    }
+}
+
+/// A run of characters in a diff.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Edit {
+    /// Present in both names.
+    Same(String),
+    /// Only in the old name.
+    Removed(String),
+    /// Only in the new name.
+    Added(String),
+}
+
+/// Computes a minimal character diff turning `old` into `new`.
+///
+/// # Examples
+///
+/// ```
+/// # use renamer::diff::{diff, Edit};
+/// assert_eq!(diff("S1E2", "S01E02"), vec![
+///     Edit::Same("S".into()),
+///     Edit::Added("0".into()),
+///     Edit::Same("1E".into()),
+///     Edit::Added("0".into()),
+///     Edit::Same("2".into()),
+/// ]);
+/// ```
+pub fn diff(old: &str, new: &str) -> Vec<Edit> {
+    let a: Vec<char> = old.chars().collect();
+    let b: Vec<char> = new.chars().collect();
+    // lcs[i][j] = length of the longest common subsequence of a[i..] and b[j..].
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+        }
+    }
+
+    let mut edits: Vec<Edit> = Vec::new();
+    let mut push = |edit: Edit| match (edits.last_mut(), edit) {
+        (Some(Edit::Same(s)), Edit::Same(c))
+        | (Some(Edit::Removed(s)), Edit::Removed(c))
+        | (Some(Edit::Added(s)), Edit::Added(c)) => s.push_str(&c),
+        (_, edit) => edits.push(edit),
+    };
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            push(Edit::Same(a[i].to_string()));
+            i += 1;
+            j += 1;
+        } else if j == b.len() || (i < a.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            push(Edit::Removed(a[i].to_string()));
+            i += 1;
+        } else {
+            push(Edit::Added(b[j].to_string()));
+            j += 1;
+        }
+    }
+    edits
+}
+
+/// Returns the old and new name with the changed characters highlighted.
+pub fn highlight(old: &str, new: &str) -> (String, String) {
+    let (mut old_out, mut new_out) = (String::new(), String::new());
+    for edit in diff(old, new) {
+        match edit {
+            Edit::Same(s) => {
+                old_out.push_str(&s);
+                new_out.push_str(&s);
+            }
+            Edit::Removed(s) => old_out.push_str(&format!("{}{}{}", RED, s, RESET)),
+            Edit::Added(s) => new_out.push_str(&format!("{}{}{}", GREEN, s, RESET)),
+        }
+    }
+    (old_out, new_out)
+}
+
+/// Formats the paths of a rename for display.
+///
+/// Without color, the paths are shown quoted as in the rest of the log output. With
+/// color, the changed parts of the file names are highlighted.
+pub fn display_rename(old: &Path, new: &Path, color: bool) -> (String, String) {
+    if !color {
+        return (format!("{:?}", old), format!("{:?}", new));
+    }
+    let dir = |p: &Path| {
+        let full = p.to_string_lossy();
+        full[..full.len() - file_name(p).len()].to_string()
+    };
+    let (old_name, new_name) = highlight(&file_name(old), &file_name(new));
+    (format!("\"{}{}\"", dir(old), old_name), format!("\"{}{}\"", dir(new), new_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(edits: &[Edit]) -> (String, String) {
+        let (mut old, mut new) = (String::new(), String::new());
+        for edit in edits {
+            match edit {
+                Edit::Same(s) => {
+                    old.push_str(s);
+                    new.push_str(s);
+                }
+                Edit::Removed(s) => old.push_str(s),
+                Edit::Added(s) => new.push_str(s),
+            }
+        }
+        (old, new)
+    }
+
+    #[test]
+    fn test_diff_reconstructs_both_names() {
+        for (old, new) in [
+            ("Show_S1E2.mkv", "Show - S01E02.mkv"),
+            ("", "new.txt"),
+            ("old.txt", ""),
+            ("same", "same"),
+            ("Épisode 1", "Episode 01"),
+        ] {
+            let edits = diff(old, new);
+            assert_eq!(apply(&edits), (old.to_string(), new.to_string()));
+            assert!(edits.windows(2).all(|w| std::mem::discriminant(&w[0]) != std::mem::discriminant(&w[1])));
+        }
+        assert_eq!(diff("same", "same"), vec![Edit::Same("same".into())]);
+    }
+
+    #[test]
+    fn test_display_rename() {
+        let old = Path::new("/d/ep1.mkv");
+        let new = Path::new("/d/ep01.mkv");
+        assert_eq!(display_rename(old, new, false), ("\"/d/ep1.mkv\"".to_string(), "\"/d/ep01.mkv\"".to_string()));
+        let (old_shown, new_shown) = display_rename(old, new, true);
+        assert_eq!(old_shown, "\"/d/ep1.mkv\"");
+        assert_eq!(new_shown, format!("\"/d/ep{}0{}1.mkv\"", GREEN, RESET));
+    }
+
+    #[test]
+    fn test_color_choice() {
+        assert!(ColorChoice::Always.enabled(false));
+        assert!(!ColorChoice::Never.enabled(true));
+        assert!(!ColorChoice::Auto.enabled(false));
+    }
+}
@@ -0,0 +1,221 @@
+//! Editor module for the renamer tool.
+//! In editor mode (`--edit`, in the style of `vidir`) the proposed new names are
+//! written to a temporary file and opened in `$VISUAL` or `$EDITOR`. The edited lines
+//! become the rename plan, which is then validated like any other plan.
+//!
+//! Each line holds the number of a file, a tab and its new name. Deleting a line
+//! leaves that file untouched; lines starting with `#` are comments.
+
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::Command;
+use anyhow::{anyhow, Result};
+use crate::file_ops::file_name;
+use crate::renamer::PlannedRename;
+
+/// Lets the user edit the new names of `plan` in their editor.
+///
+/// Returns the edited plan: entries whose line was deleted are dropped, the others
+/// get the edited name.
+///
+/// # Errors
+///
+/// Returns an error if a name cannot be edited (see [`check_editable`]), if the editor
+/// cannot be run or exits with a failure, or if the edited file is malformed (see
+/// [`apply_edits`]).
+pub fn edit_plan(plan: &[PlannedRename]) -> Result<Vec<PlannedRename>> {
+    check_editable(plan)?;
+    // A fresh file with a random name, created exclusively, so a file planted in a
+    // shared temporary directory cannot be followed or overwritten.
+    let mut file = tempfile::Builder::new()
+        .prefix("renamer-")
+        .suffix(".txt")
+        .tempfile()
+        .map_err(|e| anyhow!("Failed to create a temporary file: {}", e))?;
+    file.write_all(render_edit_file(plan).as_bytes())
+        .map_err(|e| anyhow!("Failed to write {:?}: {}", file.path(), e))?;
+    // Close the file before the editor opens it; it is removed when `path` is dropped.
+    let path = file.into_temp_path();
+    run_editor(&path)?;
+    let edited = fs::read_to_string(&path).map_err(|e| anyhow!("Failed to read {:?}: {}", path, e))?;
+    apply_edits(plan, &edited)
+}
+
+/// Checks that every current and new name of `plan` fits on one line of the edit file.
+///
+/// # Errors
+///
+/// Returns an error naming the first file whose current or new name contains a line
+/// break or a tab, as those would change the meaning of the edit file.
+///
+/// # Examples
+///
+/// ```
+/// # use renamer::PlannedRename;
+/// # use renamer::editor::check_editable;
+/// assert!(check_editable(&[PlannedRename::new("/d/a b.mkv", "/d/A.mkv")]).is_ok());
+/// assert!(check_editable(&[PlannedRename::new("/d/a\tb.mkv", "/d/A.mkv")]).is_err());
+/// ```
+pub fn check_editable(plan: &[PlannedRename]) -> Result<()> {
+    for p in plan {
+        for path in [&p.old_path, &p.new_path] {
+            let name = file_name(path);
+            if name.contains(['\n', '\r', '\t']) {
+                return Err(anyhow!(
+                    "Cannot edit the plan: the name {:?} contains a line break or tab; rename that file first or leave out --edit.",
+                    name
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the editor command: `$VISUAL`, then `$EDITOR`, then `vi`.
+pub fn editor_command() -> String {
+    ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|cmd| !cmd.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string())
+}
+
+/// Opens `path` in the editor and waits for it to exit. The editor command may carry
+/// arguments, e.g. `code --wait`.
+fn run_editor(path: &Path) -> Result<()> {
+    let command = editor_command();
+    let mut parts = command.split_whitespace();
+    let program = parts.next().unwrap_or("vi");
+    let status = Command::new(program)
+        .args(parts)
+        .arg(path)
+        .status()
+        .map_err(|e| anyhow!("Failed to run editor {:?}: {}", command, e))?;
+    if !status.success() {
+        return Err(anyhow!("Editor {:?} exited with {}; no files were renamed.", command, status));
+    }
+    Ok(())
+}
+
+/// Renders the file the user edits: a short header, then for every planned rename a
+/// comment with the current name and a line with its number and new name.
+///
+/// # Examples
+///
+/// ```
+/// # use renamer::PlannedRename;
+/// # use renamer::editor::render_edit_file;
+/// let text = render_edit_file(&[PlannedRename::new("/d/ep1.mkv", "/d/Episode 1.mkv")]);
+/// assert!(text.ends_with("# ep1.mkv\n1\tEpisode 1.mkv\n"));
+/// ```
+pub fn render_edit_file(plan: &[PlannedRename]) -> String {
+    let mut text = String::from(
+        "# Edit the new names below. Keep the number and tab at the start of each line.\n\
+         # Delete a line to leave that file untouched. Lines starting with # are ignored.\n",
+    );
+    for (idx, p) in plan.iter().enumerate() {
+        text.push_str(&format!("# {}\n{}\t{}\n", file_name(&p.old_path), idx + 1, file_name(&p.new_path)));
+    }
+    text
+}
+
+/// Turns the edited text back into a plan.
+///
+/// Renames keep their directory and the order of `plan`; the edited name is used
+/// as-is, including its extension.
+///
+/// # Errors
+///
+/// Returns an error naming the line if a line has no number, an unknown or repeated
+/// number, or a new name that is empty or contains a path separator.
+pub fn apply_edits(plan: &[PlannedRename], text: &str) -> Result<Vec<PlannedRename>> {
+    let mut names: Vec<Option<String>> = vec![None; plan.len()];
+    for (line_no, line) in text.lines().enumerate().map(|(i, l)| (i + 1, l)) {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (number, name) = line
+            .split_once('\t')
+            .ok_or_else(|| anyhow!("Line {}: expected a number, a tab and the new name", line_no))?;
+        let idx = number
+            .trim()
+            .parse::<usize>()
+            .ok()
+            .filter(|&n| n >= 1 && n <= plan.len())
+            .ok_or_else(|| anyhow!("Line {}: unknown file number {:?}", line_no, number.trim()))?
+            - 1;
+        if name.trim().is_empty() {
+            return Err(anyhow!("Line {}: the new name is empty", line_no));
+        }
+        if name.contains('/') || name.contains(std::path::MAIN_SEPARATOR) {
+            return Err(anyhow!("Line {}: the new name {:?} contains a path separator", line_no, name));
+        }
+        if names[idx].replace(name.to_string()).is_some() {
+            return Err(anyhow!("Line {}: file number {} appears more than once", line_no, idx + 1));
+        }
+    }
+    Ok(plan
+        .iter()
+        .zip(names)
+        .filter_map(|(p, name)| {
+            let name = name?;
+            Some(PlannedRename { new_path: p.old_path.with_file_name(name), ..p.clone() })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_round_trip_and_edits() {
+        let plan = vec![
+            PlannedRename::new("/d/a.mkv", "/d/A.mkv"),
+            PlannedRename::new("/d/b.mkv", "/d/B.mkv"),
+            PlannedRename::new("/d/c.mkv", "/d/C.mkv"),
+        ];
+        let text = render_edit_file(&plan);
+        assert_eq!(apply_edits(&plan, &text).unwrap(), plan);
+
+        // Edit one name, delete another and reorder the lines.
+        let edited = "# comment\n3\tThird.mkv\n\n1\tFirst (fixed).mp4\n";
+        let result = apply_edits(&plan, edited).unwrap();
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].new_path, PathBuf::from("/d/First (fixed).mp4"));
+        assert_eq!(result[1].old_path, PathBuf::from("/d/c.mkv"));
+        assert_eq!(result[1].new_path, PathBuf::from("/d/Third.mkv"));
+    }
+
+    #[test]
+    fn test_malformed_edits() {
+        let plan = vec![
+            PlannedRename::new("/d/a.mkv", "/d/A.mkv"),
+            PlannedRename::new("/d/b.mkv", "/d/B.mkv"),
+            PlannedRename::new("/d/c.mkv", "/d/C.mkv"),
+        ];
+        for (text, message) in [
+            ("1 A.mkv", "Line 1: expected a number"),
+            ("4\tD.mkv", "Line 1: unknown file number \"4\""),
+            ("x\tD.mkv", "unknown file number"),
+            ("1\t  ", "Line 1: the new name is empty"),
+            ("1\tsub/A.mkv", "contains a path separator"),
+            ("1\tA.mkv\n1\tB.mkv", "Line 2: file number 1 appears more than once"),
+        ] {
+            let err = apply_edits(&plan, text).unwrap_err().to_string();
+            assert!(err.contains(message), "{:?} gave {:?}", text, err);
+        }
+    }
+
+    #[test]
+    fn test_names_breaking_the_line_format() {
+        for (old, new) in [("/d/a\nb.mkv", "/d/A.mkv"), ("/d/a.mkv", "/d/A\r.mkv"), ("/d/a.mkv", "/d/2\tB.mkv")] {
+            let plan = vec![PlannedRename::new("/d/x.mkv", "/d/X.mkv"), PlannedRename::new(old, new)];
+            let err = check_editable(&plan).unwrap_err().to_string();
+            assert!(err.contains("contains a line break or tab"), "{}", err);
+        }
+    }
+}
@@ -16,6 +16,14 @@ use std::fmt;
 pub enum RenamerError {
     /// The provided regex pattern did not match the file name.
     InvalidPattern,
+    /// The new-name template could not be parsed.
+    InvalidTemplate(String),
+    /// A template placeholder does not match any named group of the regex.
+    UnknownPlaceholder(String),
+    /// A placeholder with arithmetic was given a value that is not an integer.
+    NotNumeric { name: String, value: String },
+    /// A rule's pattern or template is invalid.
+    InvalidRule { name: String, message: String },
     IOError(std::io::Error),
     // ... possible additional errors ...
 }
@@ -24,6 +32,14 @@ impl fmt::Display for RenamerError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             RenamerError::InvalidPattern => write!(f, "Invalid pattern provided"),
+            RenamerError::InvalidTemplate(msg) => write!(f, "Invalid template: {}", msg),
+            RenamerError::UnknownPlaceholder(name) => {
+                write!(f, "Template placeholder {{{}}} does not match any named group in the pattern", name)
+            }
+            RenamerError::NotNumeric { name, value } => {
+                write!(f, "Cannot do arithmetic on {{{}}}: value '{}' is not numeric", name, value)
+            }
+            RenamerError::InvalidRule { name, message } => write!(f, "Invalid rule {}: {}", name, message),
             RenamerError::IOError(e) => write!(f, "IO error: {}", e),
         }
     }
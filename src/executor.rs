@@ -0,0 +1,379 @@
+//! Executor module for the renamer tool.
+//! This module turns a validated rename plan into an ordered list of file system moves
+//! and performs them. Chains (a→b, b→c) are executed from their end so that no file is
+//! overwritten before it has been moved away, and cycles (a↔b, or longer rotations) are
+//! broken by parking one file under a temporary name.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use crate::renamer::{PlannedRename, RenameAction};
+
+/// A single file system move.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    /// True if an existing file at `to` may be replaced.
+    pub overwrite: bool,
+}
+
+impl Step {
+    /// Creates a move that refuses to replace an existing file.
+    pub fn new(from: impl Into<PathBuf>, to: impl Into<PathBuf>) -> Step {
+        Step { from: from.into(), to: to.into(), overwrite: false }
+    }
+}
+
+/// The outcome of executing a list of steps.
+#[derive(Debug, Default)]
+pub struct ExecutionReport {
+    /// Steps that were performed, in order.
+    pub performed: Vec<Step>,
+    /// Steps that failed, with the error message.
+    pub failed: Vec<(Step, String)>,
+    /// Reverse moves performed while rolling back after a failure, in order.
+    pub restored: Vec<Step>,
+    /// Reverse moves that failed during rollback, with the error message.
+    pub restore_failed: Vec<(Step, String)>,
+    /// Files replaced by an atomic run that could not be deleted afterwards, under the
+    /// temporary names they were set aside as, with the error message.
+    pub leftovers: Vec<(PathBuf, String)>,
+}
+
+impl ExecutionReport {
+    /// True if the run was rolled back, leaving every file at its original path.
+    pub fn rolled_back(&self) -> bool {
+        !self.failed.is_empty() && self.restored.len() == self.performed.len() && self.restore_failed.is_empty()
+    }
+}
+
+/// Orders the renames of a plan into steps that can be executed one by one.
+///
+/// The plan must be free of duplicate targets (see [`crate::plan::find_conflicts`]).
+/// Renames whose source and target are equal, and skipped renames, are dropped. Every rename whose target
+/// is the source of another rename is scheduled after that other rename; cycles are
+/// broken by first moving one of their files to a temporary name in its directory.
+///
+/// # Examples
+///
+/// ```
+/// # use renamer::PlannedRename;
+/// # use renamer::executor::order_renames;
+/// // Swap two names.
+/// let plan = vec![
+///     PlannedRename::new("/none/a", "/none/b"),
+///     PlannedRename::new("/none/b", "/none/a"),
+/// ];
+/// let steps = order_renames(&plan);
+/// assert_eq!(steps.len(), 3);
+/// assert_eq!(steps[0].from, std::path::PathBuf::from("/none/a"));
+/// assert_eq!(steps[2].to, std::path::PathBuf::from("/none/b"));
+/// ```
+pub fn order_renames(plan: &[PlannedRename]) -> Vec<Step> {
+    let renames: Vec<&PlannedRename> = plan
+        .iter()
+        .filter(|p| p.old_path != p.new_path && !p.action.is_skip())
+        .collect();
+    let by_source: HashMap<&Path, usize> = renames
+        .iter()
+        .enumerate()
+        .map(|(i, p)| (p.old_path.as_path(), i))
+        .collect();
+    // next[i] = j when rename i targets the source of rename j, so j must run first.
+    let next: Vec<Option<usize>> = renames
+        .iter()
+        .enumerate()
+        .map(|(i, p)| by_source.get(p.new_path.as_path()).copied().filter(|&j| j != i))
+        .collect();
+    let pointed: HashSet<usize> = next.iter().flatten().copied().collect();
+
+    let step = |i: usize| Step {
+        from: renames[i].old_path.clone(),
+        to: renames[i].new_path.clone(),
+        overwrite: renames[i].action == RenameAction::Overwrite,
+    };
+    let mut steps = Vec::with_capacity(renames.len());
+    let mut done = vec![false; renames.len()];
+
+    // Chains: start from every rename nobody depends on and execute back to front.
+    for head in (0..renames.len()).filter(|i| !pointed.contains(i)) {
+        let mut chain = vec![head];
+        let mut cur = head;
+        while let Some(j) = next[cur] {
+            chain.push(j);
+            cur = j;
+        }
+        for &i in chain.iter().rev() {
+            done[i] = true;
+            steps.push(step(i));
+        }
+    }
+
+    // Whatever is left forms cycles.
+    let mut reserved: HashSet<PathBuf> = renames.iter().map(|p| p.new_path.clone()).collect();
+    for start in 0..renames.len() {
+        if done[start] {
+            continue;
+        }
+        let mut cycle = Vec::new();
+        let mut cur = next[start].expect("rename left after chains is part of a cycle");
+        while cur != start {
+            cycle.push(cur);
+            cur = next[cur].expect("rename left after chains is part of a cycle");
+        }
+        let temp = temp_name(&renames[start].old_path, &reserved);
+        reserved.insert(temp.clone());
+        steps.push(Step::new(&renames[start].old_path, &temp));
+        for &i in cycle.iter().rev() {
+            done[i] = true;
+            steps.push(step(i));
+        }
+        done[start] = true;
+        steps.push(Step {
+            overwrite: renames[start].action == RenameAction::Overwrite,
+            ..Step::new(temp, &renames[start].new_path)
+        });
+    }
+    steps
+}
+
+/// Performs the steps in order, calling `on_step` after each successful move.
+///
+/// A step is refused if its target exists (unless it is an overwrite), so a failure
+/// earlier in a chain never causes a later rename to overwrite the file that could
+/// not be moved away.
+/// Execution continues after a failure; the report lists what succeeded and failed.
+pub fn execute(steps: &[Step], mut on_step: impl FnMut(&Step)) -> ExecutionReport {
+    let mut report = ExecutionReport::default();
+    for step in steps {
+        match move_file(step) {
+            Ok(()) => {
+                on_step(step);
+                report.performed.push(step.clone());
+            }
+            Err(e) => report.failed.push((step.clone(), e.to_string())),
+        }
+    }
+    report
+}
+
+/// Performs the steps in order as a single transaction.
+///
+/// Stops at the first failure and rolls back every step already performed, in
+/// reverse order, calling `on_restore` with each reverse move. The report lists the
+/// failing step, what was restored and any reverse move that could not be performed.
+///
+/// The file an overwrite replaces is first set aside under a temporary name, so a
+/// rollback can put it back; it is deleted once every step has succeeded.
+pub fn execute_atomic(
+    steps: &[Step],
+    mut on_step: impl FnMut(&Step),
+    mut on_restore: impl FnMut(&Step),
+) -> ExecutionReport {
+    let mut report = ExecutionReport::default();
+    // set_aside[i] holds the temporary name of the file replaced by the i-th step.
+    let mut set_aside: Vec<Option<PathBuf>> = Vec::new();
+    let reserved: HashSet<PathBuf> = steps.iter().map(|step| step.to.clone()).collect();
+    for step in steps {
+        let replaced = match set_aside_target(step, &reserved) {
+            Ok(replaced) => replaced,
+            Err(e) => {
+                report.failed.push((step.clone(), e.to_string()));
+                break;
+            }
+        };
+        set_aside.push(replaced);
+        match move_file(step) {
+            Ok(()) => {
+                on_step(step);
+                report.performed.push(step.clone());
+            }
+            Err(e) => {
+                report.failed.push((step.clone(), e.to_string()));
+                break;
+            }
+        }
+    }
+    if report.failed.is_empty() {
+        for temp in set_aside.into_iter().flatten() {
+            if let Err(e) = fs::remove_file(&temp) {
+                report.leftovers.push((temp, e.to_string()));
+            }
+        }
+        return report;
+    }
+    // The failing step may have set its target aside already; it is restored first.
+    for (index, replaced) in set_aside.iter().enumerate().rev() {
+        if let Some(done) = report.performed.get(index) {
+            let reverse = Step::new(&done.to, &done.from);
+            match move_file(&reverse) {
+                Ok(()) => {
+                    on_restore(&reverse);
+                    report.restored.push(reverse);
+                }
+                Err(e) => report.restore_failed.push((reverse, e.to_string())),
+            }
+        }
+        if let Some(temp) = replaced {
+            let reverse = Step::new(temp, &steps[index].to);
+            if let Err(e) = move_file(&reverse) {
+                report.restore_failed.push((reverse, e.to_string()));
+            }
+        }
+    }
+    report
+}
+
+/// Moves the file an overwrite step would replace to a temporary name next to it, and
+/// returns that name. Other steps, directories (which a rename cannot replace anyway)
+/// and targets that are the step's own source are left alone.
+fn set_aside_target(step: &Step, reserved: &HashSet<PathBuf>) -> io::Result<Option<PathBuf>> {
+    if !step.overwrite || !step.to.is_file() || same_file::is_same_file(&step.from, &step.to).unwrap_or(false) {
+        return Ok(None);
+    }
+    let temp = temp_name(&step.to, reserved);
+    fs::rename(&step.to, &temp)
+        .map_err(|e| io::Error::new(e.kind(), format!("could not set aside the existing {:?}: {}", step.to, e)))?;
+    Ok(Some(temp))
+}
+
+/// Performs a step, refusing to replace an existing file unless the step allows it.
+fn move_file(step: &Step) -> io::Result<()> {
+    if !step.overwrite && step.to.exists() && !same_file::is_same_file(&step.from, &step.to).unwrap_or(false) {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("target {:?} already exists", step.to)));
+    }
+    fs::rename(&step.from, &step.to)
+}
+
+/// Picks a temporary name next to `path` that exists neither on disk nor in `reserved`.
+fn temp_name(path: &Path, reserved: &HashSet<PathBuf>) -> PathBuf {
+    let file_name = path.file_name().map(|s| s.to_string_lossy()).unwrap_or_default();
+    (0..)
+        .map(|n| path.with_file_name(format!(".{}.renamer-{}-{}.tmp", file_name, std::process::id(), n)))
+        .find(|candidate| !reserved.contains(candidate) && !candidate.exists())
+        .expect("an unused temporary name exists")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn setup(names: &[&str]) -> (tempfile::TempDir, Vec<PathBuf>) {
+        let dir = tempdir().unwrap();
+        let paths: Vec<PathBuf> = names.iter().map(|n| dir.path().join(n)).collect();
+        for (path, name) in paths.iter().zip(names) {
+            fs::write(path, name).unwrap();
+        }
+        (dir, paths)
+    }
+
+    #[test]
+    fn test_chain_runs_back_to_front() {
+        let (dir, p) = setup(&["1", "2", "3"]);
+        let four = dir.path().join("4");
+        // Shift every name up by one: 1→2, 2→3, 3→4.
+        let plan = vec![PlannedRename::new(&p[0], &p[1]), PlannedRename::new(&p[1], &p[2]), PlannedRename::new(&p[2], &four)];
+        let steps = order_renames(&plan);
+        assert_eq!(steps[0], Step::new(&p[2], &four));
+        let report = execute(&steps, |_| {});
+        assert!(report.failed.is_empty());
+        assert_eq!(fs::read_to_string(&p[1]).unwrap(), "1");
+        assert_eq!(fs::read_to_string(&p[2]).unwrap(), "2");
+        assert_eq!(fs::read_to_string(&four).unwrap(), "3");
+        assert!(!p[0].exists());
+    }
+
+    #[test]
+    fn test_swap() {
+        let (_dir, p) = setup(&["a", "b"]);
+        let plan = vec![PlannedRename::new(&p[0], &p[1]), PlannedRename::new(&p[1], &p[0])];
+        let report = execute(&order_renames(&plan), |_| {});
+        assert!(report.failed.is_empty());
+        assert_eq!(report.performed.len(), 3);
+        assert_eq!(fs::read_to_string(&p[0]).unwrap(), "b");
+        assert_eq!(fs::read_to_string(&p[1]).unwrap(), "a");
+    }
+
+    #[test]
+    fn test_rotation_and_independent_renames() {
+        let (dir, p) = setup(&["a", "b", "c", "x"]);
+        let y = dir.path().join("y");
+        let plan = vec![
+            PlannedRename::new(&p[0], &p[1]),
+            PlannedRename::new(&p[3], &y),
+            PlannedRename::new(&p[1], &p[2]),
+            PlannedRename::new(&p[2], &p[0]),
+            PlannedRename::new(&p[0], &p[0]),
+        ];
+        let report = execute(&order_renames(&plan), |_| {});
+        assert!(report.failed.is_empty());
+        assert_eq!(fs::read_to_string(&p[1]).unwrap(), "a");
+        assert_eq!(fs::read_to_string(&p[2]).unwrap(), "b");
+        assert_eq!(fs::read_to_string(&p[0]).unwrap(), "c");
+        assert_eq!(fs::read_to_string(&y).unwrap(), "x");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 4);
+    }
+
+    #[test]
+    fn test_execute_refuses_to_overwrite() {
+        let (_dir, p) = setup(&["a", "b"]);
+        let steps = vec![Step::new(&p[0], &p[1])];
+        let report = execute(&steps, |_| {});
+        assert!(report.performed.is_empty());
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(fs::read_to_string(&p[1]).unwrap(), "b");
+    }
+
+    #[test]
+    fn test_execute_atomic_rolls_back() {
+        let (dir, p) = setup(&["a", "b", "taken"]);
+        let x = dir.path().join("x");
+        let steps = vec![
+            Step::new(&p[0], &x),
+            Step::new(&p[1], &p[2]),
+            Step::new(&x, dir.path().join("never")),
+        ];
+        let mut restored = Vec::new();
+        let report = execute_atomic(&steps, |_| {}, |s| restored.push(s.clone()));
+        assert_eq!(report.performed.len(), 1);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, steps[1]);
+        assert_eq!(report.restored, vec![Step::new(&x, &p[0])]);
+        assert_eq!(restored, report.restored);
+        assert!(report.rolled_back());
+        assert_eq!(fs::read_to_string(&p[0]).unwrap(), "a");
+        assert!(!x.exists());
+    }
+
+    #[test]
+    fn test_execute_atomic_restores_overwritten_files() {
+        let (dir, p) = setup(&["a", "x", "c", "taken"]);
+        // a replaces x, then c cannot be renamed onto the existing taken.
+        let steps = vec![Step { overwrite: true, ..Step::new(&p[0], &p[1]) }, Step::new(&p[2], &p[3])];
+        let report = execute_atomic(&steps, |_| {}, |_| {});
+        assert_eq!(report.failed.len(), 1);
+        assert!(report.rolled_back(), "{:?}", report);
+        assert_eq!(fs::read_to_string(&p[0]).unwrap(), "a");
+        assert_eq!(fs::read_to_string(&p[1]).unwrap(), "x");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 4);
+
+        // Without a failure the replaced file is gone and nothing is left behind.
+        let report = execute_atomic(&steps[..1], |_| {}, |_| {});
+        assert!(report.failed.is_empty() && report.leftovers.is_empty());
+        assert_eq!(fs::read_to_string(&p[1]).unwrap(), "a");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 3);
+    }
+
+    #[test]
+    fn test_execute_atomic_success() {
+        let (_dir, p) = setup(&["a", "b"]);
+        let plan = vec![PlannedRename::new(&p[0], &p[1]), PlannedRename::new(&p[1], &p[0])];
+        let report = execute_atomic(&order_renames(&plan), |_| {}, |_| {});
+        assert!(report.failed.is_empty());
+        assert!(!report.rolled_back());
+        assert_eq!(fs::read_to_string(&p[0]).unwrap(), "b");
+    }
+}
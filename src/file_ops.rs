@@ -1,4 +1,8 @@
-use std::path::Path;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use serde::{Deserialize, Serialize};
 
 /// Determines if the specified file should be processed based on its extension.
 /// 
@@ -14,7 +18,7 @@ use std::path::Path;
 Examples:
 
 ```
-# use std::path::Path;
+# use std::path::{Path, PathBuf};
 # use renamer::should_process_file;
 let path = Path::new("video.mkv");
 assert!(should_process_file(path, &vec!["mkv".to_string()]));
@@ -22,10 +26,52 @@ assert!(should_process_file(path, &vec!["mkv".to_string()]));
 */
 pub fn should_process_file(path: &Path, file_types: &[String]) -> bool {
     // ...existing file type check logic...
-    if let Some(ext) = path.extension() {
-        if let Some(ext_str) = ext.to_str() {
-            return file_types.iter().any(|ft| ft == ext_str);
-        }
+    if let Some(ext) = path.extension()
+        && let Some(ext_str) = ext.to_str()
+    {
+        return file_types.iter().any(|ft| ft == ext_str);
     }
     false
 }
+
+/// Makes a path absolute without resolving symlinks, so recorded paths stay valid
+/// regardless of the working directory they are used from. The path is returned as it
+/// is if the working directory cannot be determined.
+pub fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Returns the file name of `path` as a string, lossily converted, or an empty string
+/// if it has none.
+pub fn file_name(path: &Path) -> String {
+    path.file_name().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default()
+}
+
+/// A lightweight fingerprint of a file's size and modification time.
+///
+/// Used to detect whether a file has been modified between the moment it was
+/// recorded (e.g. in the undo journal) and the moment it is touched again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileStamp {
+    /// File size in bytes.
+    pub size: u64,
+    /// Modification time in nanoseconds since the Unix epoch, if the platform reports one.
+    pub mtime: Option<u64>,
+}
+
+impl FileStamp {
+    /// Reads the stamp of the file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file metadata cannot be read.
+    pub fn of(path: &Path) -> io::Result<FileStamp> {
+        let meta = fs::metadata(path)?;
+        let mtime = meta
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .and_then(|d| u64::try_from(d.as_nanos()).ok());
+        Ok(FileStamp { size: meta.len(), mtime })
+    }
+}
@@ -0,0 +1,384 @@
+//! Journal module for the renamer tool.
+//! Every non-dry-run pass records each performed rename in a persistent, append-only
+//! journal so that a run can later be reverted with `renamer undo`.
+//!
+//! The journal is a JSON Lines file: one [`JournalEntry`] per line. Entries belonging
+//! to the same invocation share a run id.
+
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use anyhow::{anyhow, Result};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use crate::file_ops::{absolute, FileStamp};
+
+/// A single rename recorded in the journal.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// Identifier shared by all renames performed in one invocation.
+    pub run_id: String,
+    /// Local time at which the rename was performed (RFC 3339).
+    pub timestamp: String,
+    pub old_path: PathBuf,
+    pub new_path: PathBuf,
+    /// Stamp of the file right after it was renamed, used to detect later modifications.
+    pub stamp: Option<FileStamp>,
+    /// Set when this run was itself an undo of another run.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub undo_of: Option<String>,
+}
+
+/// An open journal that renames of the current run are appended to.
+#[derive(Debug)]
+pub struct Journal {
+    path: PathBuf,
+    run_id: String,
+    undo_of: Option<String>,
+    file: Option<File>,
+}
+
+impl Journal {
+    /// Returns the default journal location.
+    ///
+    /// Uses `$XDG_DATA_HOME/renamer/journal.jsonl`, falling back to
+    /// `$HOME/.local/share/renamer/journal.jsonl` (or `%LOCALAPPDATA%` on Windows).
+    pub fn default_path() -> PathBuf {
+        let data_dir = std::env::var_os("XDG_DATA_HOME")
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("LOCALAPPDATA").map(PathBuf::from))
+            .or_else(|| std::env::var_os("HOME").map(|h| Path::new(&h).join(".local").join("share")))
+            .unwrap_or_else(|| PathBuf::from("."));
+        data_dir.join("renamer").join("journal.jsonl")
+    }
+
+    /// Starts a new run that will append to the journal at `path`.
+    ///
+    /// The file is only created once the first rename is recorded, so runs that
+    /// rename nothing leave no trace.
+    pub fn begin(path: &Path) -> Journal {
+        Journal {
+            path: path.to_path_buf(),
+            run_id: Local::now().format("%Y%m%d-%H%M%S-%6f").to_string(),
+            undo_of: None,
+            file: None,
+        }
+    }
+
+    /// Starts a run that reverts `run_id`, such as the rollback of a failed
+    /// all-or-nothing run. The run is listed as an undo of `run_id`.
+    pub fn begin_undo(path: &Path, run_id: &str) -> Journal {
+        Journal { undo_of: Some(run_id.to_string()), ..Journal::begin(path) }
+    }
+
+    /// The identifier of the current run.
+    pub fn run_id(&self) -> &str {
+        &self.run_id
+    }
+
+    /// Appends a performed rename to the journal and flushes it to disk.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the journal file cannot be created or written.
+    pub fn record(&mut self, old_path: &Path, new_path: &Path) -> io::Result<()> {
+        if self.file.is_none() {
+            if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                fs::create_dir_all(parent)?;
+            }
+            self.file = Some(OpenOptions::new().create(true).append(true).open(&self.path)?);
+        }
+        let entry = JournalEntry {
+            run_id: self.run_id.clone(),
+            timestamp: Local::now().to_rfc3339(),
+            old_path: absolute(old_path),
+            new_path: absolute(new_path),
+            stamp: FileStamp::of(new_path).ok(),
+            undo_of: self.undo_of.clone(),
+        };
+        let line = serde_json::to_string(&entry).map_err(io::Error::other)?;
+        let file = self.file.as_mut().expect("journal file opened above");
+        writeln!(file, "{}", line)?;
+        file.flush()
+    }
+}
+
+/// A summary of one run found in the journal.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunSummary {
+    pub run_id: String,
+    /// Timestamp of the first rename in the run.
+    pub timestamp: String,
+    /// Number of renames performed by the run.
+    pub count: usize,
+    /// The run this run reverted, if it was an undo.
+    pub undo_of: Option<String>,
+    /// True if a later undo run reverted this run.
+    pub undone: bool,
+}
+
+/// The outcome of an undo.
+#[derive(Debug, Default)]
+pub struct UndoReport {
+    /// The run that was reverted.
+    pub run_id: String,
+    /// Renames that were reverted, as `(current path, restored path)`.
+    pub restored: Vec<(PathBuf, PathBuf)>,
+    /// Entries that could not be reverted, with the reason.
+    pub skipped: Vec<(JournalEntry, String)>,
+}
+
+/// Reads every entry of the journal at `path`.
+///
+/// A missing journal is treated as empty.
+///
+/// # Errors
+///
+/// Returns an error if the journal cannot be read or a line is not a valid entry.
+pub fn read_entries(path: &Path) -> Result<Vec<JournalEntry>> {
+    let file = match File::open(path) {
+        Ok(f) => f,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(anyhow!("Failed to open journal {:?}: {}", path, e)),
+    };
+    let mut entries = Vec::new();
+    for (idx, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry = serde_json::from_str(&line)
+            .map_err(|e| anyhow!("Corrupt journal {:?} at line {}: {}", path, idx + 1, e))?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Groups journal entries into runs, in the order the runs were performed.
+pub fn list_runs(entries: &[JournalEntry]) -> Vec<RunSummary> {
+    let undone: HashSet<&str> = entries.iter().filter_map(|e| e.undo_of.as_deref()).collect();
+    let mut runs: Vec<RunSummary> = Vec::new();
+    for entry in entries {
+        match runs.iter_mut().find(|r| r.run_id == entry.run_id) {
+            Some(run) => run.count += 1,
+            None => runs.push(RunSummary {
+                run_id: entry.run_id.clone(),
+                timestamp: entry.timestamp.clone(),
+                count: 1,
+                undo_of: entry.undo_of.clone(),
+                undone: undone.contains(entry.run_id.as_str()),
+            }),
+        }
+    }
+    runs
+}
+
+/// Tracks the paths created and vacated by the renames an undo has already planned,
+/// so that chains (a→b, b→c) are checked against the state they will actually see.
+#[derive(Default)]
+struct Replay {
+    added: HashSet<PathBuf>,
+    removed: HashSet<PathBuf>,
+}
+
+impl Replay {
+    fn exists(&self, path: &Path) -> bool {
+        self.added.contains(path) || (!self.removed.contains(path) && path.exists())
+    }
+
+    fn apply(&mut self, from: &Path, to: &Path) {
+        self.added.remove(from);
+        self.removed.insert(from.to_path_buf());
+        self.removed.remove(to);
+        self.added.insert(to.to_path_buf());
+    }
+
+    /// Checks whether a journal entry can still be reverted safely.
+    ///
+    /// Returns the reason if the file has been moved or modified since it was renamed,
+    /// or if its original path is now occupied.
+    fn check(&self, entry: &JournalEntry) -> Option<String> {
+        if !self.exists(&entry.new_path) {
+            return Some(format!("{:?} no longer exists (moved or deleted)", entry.new_path));
+        }
+        if self.exists(&entry.old_path) {
+            return Some(format!("original path {:?} is occupied", entry.old_path));
+        }
+        if let Some(stamp) = entry.stamp.filter(|_| !self.added.contains(&entry.new_path)) {
+            match FileStamp::of(&entry.new_path) {
+                Ok(current) if current == stamp => {}
+                Ok(_) => return Some(format!("{:?} was modified after the rename", entry.new_path)),
+                Err(e) => return Some(format!("cannot read {:?}: {}", entry.new_path, e)),
+            }
+        }
+        None
+    }
+}
+
+/// Reverts a run recorded in the journal at `journal_path`.
+///
+/// When `run_id` is `None`, the most recent run that is neither an undo nor already
+/// undone is chosen. Entries are replayed in reverse order. If any entry fails the
+/// safety checks (the file was moved or modified since the rename, or its original
+/// path is occupied again), the undo is refused unless `force` is set, in
+/// which case only the intact entries are reverted. With `dry_run`, nothing is touched.
+/// The undo itself is journaled as a new run so it can be reverted in turn.
+///
+/// # Errors
+///
+/// Returns an error if the run cannot be found, the safety checks fail without
+/// `force`, or a rename fails.
+pub fn undo(journal_path: &Path, run_id: Option<&str>, dry_run: bool, force: bool) -> Result<UndoReport> {
+    let entries = read_entries(journal_path)?;
+    let runs = list_runs(&entries);
+    let run = match run_id {
+        Some(id) => runs
+            .iter()
+            .find(|r| r.run_id == id)
+            .ok_or_else(|| anyhow!("No run with id {} in journal {:?}", id, journal_path))?,
+        None => runs
+            .iter()
+            .rev()
+            .find(|r| r.undo_of.is_none() && !r.undone)
+            .ok_or_else(|| anyhow!("No run left to undo in journal {:?}", journal_path))?,
+    };
+
+    let mut report = UndoReport { run_id: run.run_id.clone(), ..Default::default() };
+    let mut replay = Replay::default();
+    let mut todo = Vec::new();
+    for entry in entries.iter().rev().filter(|e| e.run_id == run.run_id) {
+        match replay.check(entry) {
+            Some(reason) => report.skipped.push((entry.clone(), reason)),
+            None => {
+                replay.apply(&entry.new_path, &entry.old_path);
+                todo.push(entry);
+            }
+        }
+    }
+    if !report.skipped.is_empty() && !force {
+        let reasons: Vec<String> = report.skipped.iter().map(|(_, r)| format!("  - {}", r)).collect();
+        return Err(anyhow!(
+            "Refusing to undo run {}: {} file(s) changed since the rename:\n{}",
+            run.run_id,
+            report.skipped.len(),
+            reasons.join("\n")
+        ));
+    }
+
+    let mut journal = Journal::begin_undo(journal_path, &run.run_id);
+    for entry in todo {
+        if !dry_run {
+            fs::rename(&entry.new_path, &entry.old_path)
+                .map_err(|e| anyhow!("Failed to restore {:?} to {:?}: {}", entry.new_path, entry.old_path, e))?;
+            journal.record(&entry.new_path, &entry.old_path)?;
+        }
+        report.restored.push((entry.new_path.clone(), entry.old_path.clone()));
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn rename_and_record(journal: &mut Journal, old: &Path, new: &Path) {
+        fs::rename(old, new).unwrap();
+        journal.record(old, new).unwrap();
+    }
+
+    #[test]
+    fn test_record_and_list_runs() {
+        let dir = tempdir().unwrap();
+        let journal_path = dir.path().join("journal.jsonl");
+        let a = dir.path().join("a.txt");
+        let b = dir.path().join("b.txt");
+        fs::write(&a, "a").unwrap();
+
+        let mut journal = Journal::begin(&journal_path);
+        rename_and_record(&mut journal, &a, &b);
+
+        let entries = read_entries(&journal_path).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].old_path, a);
+        assert_eq!(entries[0].new_path, b);
+        let runs = list_runs(&entries);
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].run_id, journal.run_id());
+        assert_eq!(runs[0].count, 1);
+        assert!(!runs[0].undone);
+    }
+
+    #[test]
+    fn test_missing_journal_is_empty() {
+        let dir = tempdir().unwrap();
+        assert!(read_entries(&dir.path().join("none.jsonl")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_undo_restores_latest_run() {
+        let dir = tempdir().unwrap();
+        let journal_path = dir.path().join("journal.jsonl");
+        let a = dir.path().join("a.txt");
+        let b = dir.path().join("b.txt");
+        let c = dir.path().join("c.txt");
+        fs::write(&a, "a").unwrap();
+
+        let mut journal = Journal::begin(&journal_path);
+        rename_and_record(&mut journal, &a, &b);
+        rename_and_record(&mut journal, &b, &c);
+
+        let report = undo(&journal_path, None, false, false).unwrap();
+        assert_eq!(report.restored.len(), 2);
+        assert!(a.exists());
+        assert!(!b.exists());
+        assert!(!c.exists());
+
+        let runs = list_runs(&read_entries(&journal_path).unwrap());
+        assert_eq!(runs.len(), 2);
+        assert!(runs[0].undone);
+        assert_eq!(runs[1].undo_of.as_deref(), Some(journal.run_id()));
+        // Nothing left to undo by default.
+        assert!(undo(&journal_path, None, false, false).is_err());
+    }
+
+    #[test]
+    fn test_undo_refuses_modified_files() {
+        let dir = tempdir().unwrap();
+        let journal_path = dir.path().join("journal.jsonl");
+        let a = dir.path().join("a.txt");
+        let b = dir.path().join("b.txt");
+        fs::write(&a, "a").unwrap();
+
+        let mut journal = Journal::begin(&journal_path);
+        rename_and_record(&mut journal, &a, &b);
+        fs::write(&b, "changed contents").unwrap();
+
+        assert!(undo(&journal_path, None, false, false).is_err());
+        assert!(b.exists());
+
+        let report = undo(&journal_path, None, false, true).unwrap();
+        assert!(report.restored.is_empty());
+        assert_eq!(report.skipped.len(), 1);
+        assert!(b.exists());
+    }
+
+    #[test]
+    fn test_undo_dry_run_touches_nothing() {
+        let dir = tempdir().unwrap();
+        let journal_path = dir.path().join("journal.jsonl");
+        let a = dir.path().join("a.txt");
+        let b = dir.path().join("b.txt");
+        fs::write(&a, "a").unwrap();
+
+        let mut journal = Journal::begin(&journal_path);
+        rename_and_record(&mut journal, &a, &b);
+
+        let report = undo(&journal_path, Some(journal.run_id()), true, false).unwrap();
+        assert_eq!(report.restored, vec![(b.clone(), a.clone())]);
+        assert!(b.exists());
+        assert!(!a.exists());
+    }
+}
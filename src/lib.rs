@@ -1,11 +1,24 @@
 pub mod cli;
 pub mod config;
+pub mod diff;
+pub mod editor;
 pub mod error;  // Keep this module
+pub mod executor;
 pub mod file_ops;
+pub mod journal;
+pub mod plan;
+pub mod plan_file;
+pub mod preset;
 pub mod renamer;
+pub mod report;
+pub mod review;
+pub mod rule;
+pub mod template;
 
 pub use cli::Cli;
 pub use config::merge_config;
 pub use error::RenamerError;  // Export from error module
-pub use renamer::{PlannedRename, transform_filename, check_warning};
+pub use renamer::{PlannedRename, RenameAction, transform_filename, check_warning};
+pub use template::Template;
 pub use file_ops::should_process_file;
+pub use journal::Journal;
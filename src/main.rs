@@ -1,64 +1,140 @@
 //! Main module for the renamer tool.
 //! This module handles the CLI parsing, logging setup, and the main logic for processing files.
 
-mod error; 
-mod cli;
-mod renamer;
-mod config; 
-
 use log::{info, warn, error, LevelFilter};
-use simplelog::{Config, SimpleLogger};
-use walkdir::WalkDir;
-use std::io::{self, Write};
+use simplelog::{Config, SimpleLogger, WriteLogger};
+use std::collections::BTreeMap;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
 use clap::Parser;
 use anyhow::{anyhow, Result};
-use rayon::prelude::*;
 
-use crate::cli::Cli;
-use crate::config::merge_config;
-use crate::renamer::{PlannedRename, transform_filename, check_warning, should_process_file};
+use renamer::cli::{ApplyArgs, Cli, Command, UndoArgs};
+use renamer::config::merge_config;
+use renamer::diff::display_rename;
+use renamer::editor::edit_plan;
+use renamer::executor::{execute, execute_atomic, order_renames};
+use renamer::journal::{self, Journal};
+use renamer::plan::{build_plan, collect_files, conflict_report, find_conflicts, resolve_conflicts, sort_paths, Sequence};
+use renamer::plan_file::PlanFile;
+use renamer::preset::{self, Preset};
+use renamer::renamer::{PlannedRename, RenameAction};
+use renamer::report::{plan_rows, result_rows, write_rows};
+use renamer::review::{review, review_resolved};
+use renamer::rule::{Rule, RuleSpec};
 
 fn main() -> Result<()> {
-    SimpleLogger::init(LevelFilter::Info, Config::default())?;
     let mut cli = Cli::parse();
+    let mut presets = BTreeMap::new();
+    if !matches!(cli.command, Some(Command::Undo(_))) {
+        let settings = merge_config(&mut cli)?;
+        if cli.show_config {
+            print!("{}", settings);
+            return Ok(());
+        }
+        presets = settings.values.presets.unwrap_or_default();
+    }
+
+    // With a machine-readable format, stdout carries the report and log messages go to stderr.
+    if cli.format.is_some() {
+        WriteLogger::init(LevelFilter::Info, Config::default(), io::stderr())?;
+    } else {
+        SimpleLogger::init(LevelFilter::Info, Config::default())?;
+    }
 
-    merge_config(&mut cli)?; // Use merge_config from config module
+    let journal_path = cli.journal.clone().unwrap_or_else(Journal::default_path);
+    // An exported plan is meant to be reviewed before it runs, so nothing is renamed now.
+    if cli.plan_out.is_some() {
+        cli.dry_run = true;
+    }
+    match &cli.command {
+        Some(Command::Undo(args)) => return run_undo(&journal_path, args, cli.dry_run),
+        Some(Command::Test(args)) => return run_test(&cli, &args.names),
+        Some(Command::Check) => return run_check(&cli),
+        Some(Command::Presets) => return run_presets(&presets),
+        Some(Command::Plan) => cli.dry_run = true,
+        Some(Command::Apply(ApplyArgs { plan: Some(plan) })) => return run_saved_plan(&cli, &journal_path, plan),
+        Some(Command::Apply(_)) | None => {}
+    }
+    run_rename(&cli, &journal_path)
+}
 
+/// Plans the renames described by the options and, unless in dry-run mode, performs them.
+fn run_rename(cli: &Cli, journal_path: &Path) -> Result<()> {
     info!("Starting renamer tool with parameters: {:?}", cli);
 
-    // Compile the provided regex pattern.
-    let re = regex::Regex::new(&cli.current_pattern)
-        .map_err(|e| anyhow!("Invalid regex pattern provided for current file names: {}", e))?;
-
-    // Remove title-related variable that's no longer used
-
-    // Replace the sequential iteration with parallel processing.
-    let planned: Vec<PlannedRename> = WalkDir::new(&cli.directory)
-        .max_depth(cli.depth)
-        .into_iter()
-        .filter_map(|e| e.ok())
-        .par_bridge()  // Converts the iterator into a parallel iterator.
-        .filter_map(|entry| {
-            let path = entry.path();
-            if path.is_file() && should_process_file(path, &cli.file_types) {
-                if let Some(file_name) = path.file_name().and_then(|s| s.to_str()) {
-                    if let Ok(new_file_name) = transform_filename(file_name, &cli.new_pattern, &re) {
-                        let warn = check_warning(file_name, &re);
-                        let new_path = path.with_file_name(&new_file_name);
-                        return Some(PlannedRename {
-                            old_path: path.to_path_buf(),
-                            new_path: new_path.clone(),
-                            warn,
-                        });
-                    }
-                }
-            }
-            None
-        })
-        .collect();
+    // Build the plan in a fixed order so output, `{n}` numbering and conflict suffixes are reproducible.
+    let mut files = collect_files(cli.directory(), cli.depth(), &cli.file_types);
+    sort_paths(&mut files, cli.sort.unwrap_or_default());
+    let mut planned = if cli.edit && cli.rule_specs().is_empty() {
+        // Editor mode without a pattern: start from the current names.
+        files.iter().map(|f| PlannedRename::new(f, f)).collect()
+    } else {
+        let (planned, errors) = build_plan(&files, &compile(cli)?, &sequence(cli));
+        for (path, e) in &errors {
+            warn!("Skipping {:?}: {}", path, e);
+        }
+        planned
+    };
+    if cli.edit {
+        planned = edit_plan(&planned)?;
+        planned.retain(|p| p.old_path != p.new_path);
+    }
+
+    // Validate the whole plan before touching any file, resolving conflicts per the chosen policy.
+    let policy = cli.on_conflict.unwrap_or_default();
+    let mut conflicts = resolve_conflicts(&mut planned, policy);
+    if cli.interactive {
+        // Review the resolved names and actions; edited names are resolved again and shown if that changes them.
+        let Some(reviewed) = review_resolved(&planned, policy, &mut io::stdin().lock(), &mut io::stderr())? else {
+            warn!("Aborting as per user request.");
+            return Ok(());
+        };
+        (planned, conflicts) = reviewed;
+    }
+    if let Some(plan_out) = &cli.plan_out {
+        PlanFile::new(&planned, &conflicts).save(plan_out)?;
+        info!("Wrote rename plan to {:?}.", plan_out);
+    }
+    if !conflicts.is_empty() {
+        error!("The rename plan has conflicts:\n{}", conflict_report(&conflicts));
+        return Err(anyhow!("Aborting: {} conflict(s) found, no files were renamed.", conflicts.len()));
+    }
+    perform(cli, journal_path, &planned)
+}
 
-    // If any file would be renamed with season or episode "0", warn the user.
-    if planned.iter().any(|p| p.warn) {
+/// Performs a plan saved with `--plan-out`, after checking that its source files are
+/// unchanged and that it is still free of conflicts.
+fn run_saved_plan(cli: &Cli, journal_path: &Path, path: &Path) -> Result<()> {
+    let plan_file = PlanFile::load(path)?;
+    let changed = plan_file.changed_sources();
+    if !changed.is_empty() {
+        for (source, reason) in &changed {
+            error!("{:?}: {}", source, reason);
+        }
+        return Err(anyhow!("Aborting: {} file(s) changed since the plan was made, no files were renamed.", changed.len()));
+    }
+    let mut planned = plan_file.renames();
+    if cli.interactive {
+        let Some(reviewed) = review(&planned, &mut io::stdin().lock(), &mut io::stderr())? else {
+            warn!("Aborting as per user request.");
+            return Ok(());
+        };
+        planned = reviewed;
+    }
+    let conflicts = find_conflicts(&planned);
+    if !conflicts.is_empty() {
+        error!("The rename plan has conflicts:\n{}", conflict_report(&conflicts));
+        return Err(anyhow!("Aborting: {} conflict(s) found, no files were renamed.", conflicts.len()));
+    }
+    info!("Applying rename plan {:?} made {}.", path, plan_file.created);
+    perform(cli, journal_path, &planned)
+}
+
+/// Asks for confirmation if needed and, unless in dry-run mode, performs a conflict-free plan.
+fn perform(cli: &Cli, journal_path: &Path, planned: &[PlannedRename]) -> Result<()> {
+    // If any file would be renamed with season or episode "0", warn the user (already done per file in interactive mode).
+    if !cli.interactive && planned.iter().any(|p| p.warn) {
         warn!("Some files have season or episode as 0. This might be unintended.");
         eprint!("Do you want to proceed? (y/N): ");
         io::stdout().flush()?;
@@ -71,17 +147,198 @@ fn main() -> Result<()> {
         }
     }
 
-    // Process the planned renames.
+    // Highlight what each rename changes when the log goes to a terminal.
+    let color = cli.dry_run && cli.format.is_none() && cli.color.unwrap_or_default().enabled(io::stdout().is_terminal());
     for plan in planned {
-        info!("Renaming from {:?} to {:?}", plan.old_path, plan.new_path);
-        if cli.dry_run {
-            info!("Dry-run mode: no changes made.");
-        } else {
-            if let Err(e) = std::fs::rename(&plan.old_path, &plan.new_path) {
-                error!("Error renaming file: {:?}", e);
+        let (old, new) = display_rename(&plan.old_path, &plan.new_path, color);
+        match &plan.action {
+            RenameAction::Skip(reason) => warn!("Skipping {:?}: {}", plan.old_path, reason),
+            RenameAction::Overwrite if cli.dry_run => {
+                info!("Renaming from {} to {} (overwriting existing file){}", old, new, rule_note(cli, plan))
+            }
+            _ if cli.dry_run => info!("Renaming from {} to {}{}", old, new, rule_note(cli, plan)),
+            _ => {}
+        }
+    }
+    if cli.dry_run {
+        if let Some(format) = cli.format {
+            write_rows(&mut io::stdout().lock(), &plan_rows(planned), format)?;
+        }
+        info!("Dry-run mode: no changes made.");
+        return Ok(());
+    }
+
+    // Execute the renames in a chain- and cycle-safe order, recording each move in the undo journal.
+    let steps = order_renames(planned);
+    let mut journal = Journal::begin(journal_path);
+    let run_id = journal.run_id().to_string();
+    let on_step = |step: &renamer::executor::Step| {
+        info!("Renaming from {:?} to {:?}", step.from, step.to);
+        if let Err(e) = journal.record(&step.from, &step.to) {
+            error!("Failed to write undo journal {:?}: {:?}", journal_path, e);
+        }
+    };
+    let report = if cli.atomic {
+        // Rollback moves are journaled as an undo of this run.
+        let mut rollback_journal = Journal::begin_undo(journal_path, &run_id);
+        execute_atomic(&steps, on_step, |step| {
+            if let Err(e) = rollback_journal.record(&step.from, &step.to) {
+                error!("Failed to write undo journal {:?}: {:?}", journal_path, e);
+            }
+        })
+    } else {
+        execute(&steps, on_step)
+    };
+    for (step, e) in &report.failed {
+        error!("Error renaming {:?} to {:?}: {}", step.from, step.to, e);
+    }
+    for (path, e) in &report.leftovers {
+        warn!("Could not delete the replaced file, kept as {:?}: {}", path, e);
+    }
+    if let Some(format) = cli.format {
+        write_rows(&mut io::stdout().lock(), &result_rows(planned, &report), format)?;
+    }
+    if cli.atomic && !report.failed.is_empty() {
+        for step in &report.restored {
+            info!("Restored {:?} to {:?}", step.from, step.to);
+        }
+        for (step, e) in &report.restore_failed {
+            error!("Could not restore {:?} to {:?}: {}", step.from, step.to, e);
+        }
+        if report.rolled_back() {
+            return Err(anyhow!("Rename failed; rolled back all {} rename(s) performed in run {}.", report.restored.len(), run_id));
+        }
+        return Err(anyhow!(
+            "Rename failed; rollback restored {} of {} rename(s) in run {}, {} could not be restored.",
+            report.restored.len(), report.performed.len(), run_id, report.restore_failed.len()
+        ));
+    }
+    if !report.performed.is_empty() {
+        info!("Renamed {} file(s) in run {}; revert with `renamer undo --run {}`.", report.performed.len(), run_id, run_id);
+    }
+
+    Ok(())
+}
+
+/// Compiles the rules: --current-pattern/--new-pattern first, then the --rule options
+/// (or the config file's rules), checking that every placeholder of each template is a
+/// named group of its pattern.
+fn compile(cli: &Cli) -> Result<Vec<Rule>> {
+    let specs = cli.rule_specs();
+    if specs.is_empty() {
+        return Err(anyhow!(
+            "No current file name pattern given: pass --current-pattern or --rule, or set current_pattern or rules in the config file."
+        ));
+    }
+    Ok(specs.iter().enumerate().map(|(i, spec)| Rule::compile(spec, i)).collect::<Result<_, _>>()?)
+}
+
+/// Names the rule that matched a file when there is more than one rule.
+fn rule_note(cli: &Cli, plan: &PlannedRename) -> String {
+    match &plan.rule {
+        Some(rule) if cli.rule_specs().len() > 1 => format!(" (rule {})", rule),
+        _ => String::new(),
+    }
+}
+
+/// The `{n}` counter settings; files are numbered in plan order unless `--counter-sort` is given.
+fn sequence(cli: &Cli) -> Sequence {
+    Sequence {
+        start: cli.counter_start.unwrap_or(1),
+        step: cli.counter_step.unwrap_or(1),
+        sort: cli.counter_sort.or(cli.sort).unwrap_or_default(),
+    }
+}
+
+/// Prints the new name of each sample file name without touching the disk.
+fn run_test(cli: &Cli, names: &[String]) -> Result<()> {
+    let rules = compile(cli)?;
+    let files: Vec<PathBuf> = names.iter().map(PathBuf::from).collect();
+    let (planned, errors) = build_plan(&files, &rules, &sequence(cli));
+    for file in &files {
+        if let Some(plan) = planned.iter().find(|p| &p.old_path == file) {
+            match (&plan.rule, rules.len()) {
+                (Some(rule), 2..) => info!("{:?} -> {:?} (rule {})", file, plan.new_path, rule),
+                _ => info!("{:?} -> {:?}", file, plan.new_path),
+            }
+            if plan.warn {
+                warn!("{:?} has season or episode as 0. This might be unintended.", file);
             }
+        } else if let Some((_, e)) = errors.iter().find(|(path, _)| path == file) {
+            warn!("{:?}: {}", file, e);
+        } else {
+            info!("{:?} does not match any pattern.", file);
+        }
+    }
+    Ok(())
+}
+
+/// Prints every preset with its pattern and template; `custom` are the presets defined
+/// in config files.
+fn run_presets(custom: &BTreeMap<String, RuleSpec>) -> Result<()> {
+    for name in preset::names(custom) {
+        let Some(spec) = preset::resolve(name, custom) else { continue };
+        let builtin = Preset::find(name);
+        let description = match (builtin, custom.contains_key(name)) {
+            (Some(p), false) => p.description,
+            (Some(_), true) => "overridden in a config file",
+            (None, _) => "defined in a config file",
+        };
+        println!("{}  {}", name, description);
+        println!("    pattern:  {}", spec.pattern);
+        println!("    template: {}", spec.template);
+        if let Some(p) = builtin.filter(|_| !custom.contains_key(name)) {
+            println!("    example:  {} -> {}", p.example.0, p.example.1);
+        }
+    }
+    Ok(())
+}
+
+/// Validates the configuration without walking the directory.
+fn run_check(cli: &Cli) -> Result<()> {
+    let rules = compile(cli)?;
+    if !cli.directory().is_dir() {
+        return Err(anyhow!("Directory {:?} does not exist.", cli.directory()));
+    }
+    for rule in &rules {
+        info!(
+            "Rule {}: pattern {:?} with {} named group(s), template {:?}.",
+            rule.name, rule.pattern.as_str(), rule.pattern.capture_names().flatten().count(), rule.template.to_string()
+        );
+    }
+    info!("Configuration is valid: {} rule(s), directory {:?}.", rules.len(), cli.directory());
+    Ok(())
+}
+
+/// Lists or reverts runs recorded in the undo journal.
+fn run_undo(journal_path: &Path, args: &UndoArgs, dry_run: bool) -> Result<()> {
+    if args.list {
+        let runs = journal::list_runs(&journal::read_entries(journal_path)?);
+        if runs.is_empty() {
+            info!("No runs recorded in journal {:?}.", journal_path);
+        }
+        for run in runs {
+            let status = match (&run.undo_of, run.undone) {
+                (Some(of), _) => format!("undo of {}", of),
+                (None, true) => "undone".to_string(),
+                (None, false) => "applied".to_string(),
+            };
+            info!("{}  {}  {} file(s)  {}", run.run_id, run.timestamp, run.count, status);
         }
+        return Ok(());
     }
 
+    let report = journal::undo(journal_path, args.run.as_deref(), dry_run, args.force)?;
+    for (entry, reason) in &report.skipped {
+        warn!("Skipping {:?}: {}", entry.new_path, reason);
+    }
+    for (from, to) in &report.restored {
+        info!("Restoring {:?} to {:?}", from, to);
+    }
+    if dry_run {
+        info!("Dry-run mode: no changes made.");
+    } else {
+        info!("Undid run {}: {} file(s) restored, {} skipped.", report.run_id, report.restored.len(), report.skipped.len());
+    }
     Ok(())
 }
@@ -0,0 +1,675 @@
+//! Plan module for the renamer tool.
+//! This module builds the rename plan for the files of a directory, validates the
+//! complete plan before any file is touched, detecting renames that would collide
+//! with each other or clobber files already on disk, and resolves those conflicts
+//! according to a [`ConflictPolicy`].
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use clap::ValueEnum;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+use crate::config::PROJECT_CONFIG;
+use crate::error::RenamerError;
+use crate::file_ops::FileStamp;
+use crate::renamer::{PlannedRename, RenameAction, check_warning, enforce_extension, should_process_file};
+use crate::rule::Rule;
+
+/// The order of the files in a rename plan, and in which they are numbered by `{n}`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortKey {
+    /// Byte-wise order of the paths.
+    Name,
+    /// Natural order of the paths, comparing runs of digits by value ("2" before "10").
+    #[default]
+    Natural,
+    /// Oldest modification time first.
+    Mtime,
+    /// Smallest file first.
+    Size,
+}
+
+/// Settings of the `{n}` sequence counter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sequence {
+    /// Value given to the first file.
+    pub start: i64,
+    /// Increment between consecutive files.
+    pub step: i64,
+    /// Order in which files are numbered.
+    pub sort: SortKey,
+}
+
+impl Default for Sequence {
+    fn default() -> Self {
+        Sequence { start: 1, step: 1, sort: SortKey::default() }
+    }
+}
+
+/// Collects the files under `directory` (down to `depth` levels) whose extension is
+/// one of `file_types`, or all files if `file_types` is empty. Config files named
+/// [`PROJECT_CONFIG`] are never collected.
+pub fn collect_files(directory: &Path, depth: usize, file_types: &[String]) -> Vec<PathBuf> {
+    WalkDir::new(directory)
+        .max_depth(depth)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|entry| entry.file_type().is_file() && entry.file_name() != PROJECT_CONFIG)
+        .filter(|entry| should_process_file(entry.path(), file_types))
+        .map(|entry| entry.into_path())
+        .collect()
+}
+
+/// Builds the rename plan for `files`.
+///
+/// Each file is renamed by the first of `rules` whose pattern matches its name; files
+/// matching no rule are left out. Matching files are numbered for the `{n}` counter in
+/// the order given by `sequence.sort`, so the result does not depend on the order of
+/// `files`; the plan itself keeps the order of `files` and records the rule used for
+/// each file. Files whose new name cannot be rendered (e.g. arithmetic on a
+/// non-numeric capture) are returned separately with the error.
+///
+/// # Examples
+///
+/// ```
+/// # use std::path::PathBuf;
+/// # use renamer::rule::Rule;
+/// # use renamer::plan::{build_plan, Sequence};
+/// let rules = vec![Rule::compile(&"^scan=>Page {n:02}".parse().unwrap(), 0).unwrap()];
+/// let files = vec![PathBuf::from("/none/scan10.pdf"), PathBuf::from("/none/scan9.pdf")];
+/// let (plan, errors) = build_plan(&files, &rules, &Sequence::default());
+/// assert!(errors.is_empty());
+/// assert_eq!(plan[0].new_path, PathBuf::from("/none/Page 02.pdf"));
+/// assert_eq!(plan[1].new_path, PathBuf::from("/none/Page 01.pdf"));
+/// assert_eq!(plan[1].rule.as_deref(), Some("#1"));
+/// ```
+pub fn build_plan(
+    files: &[PathBuf],
+    rules: &[Rule],
+    sequence: &Sequence,
+) -> (Vec<PlannedRename>, Vec<(PathBuf, RenamerError)>) {
+    let matched: Vec<(&Path, &str, &Rule)> = files
+        .iter()
+        .filter_map(|path| {
+            let name = path.file_name().and_then(|s| s.to_str())?;
+            Rule::first_match(rules, name).map(|rule| (path.as_path(), name, rule))
+        })
+        .collect();
+
+    let mut counters = vec![None; matched.len()];
+    if rules.iter().any(|rule| rule.template.uses_counter()) {
+        let paths: Vec<&Path> = matched.iter().map(|(path, _, _)| *path).collect();
+        let mut value = sequence.start;
+        for idx in sorted_indices(&paths, sequence.sort) {
+            counters[idx] = Some(value);
+            value = value.saturating_add(sequence.step);
+        }
+    }
+
+    let results: Vec<Result<PlannedRename, (PathBuf, RenamerError)>> = matched
+        .par_iter()
+        .zip(counters.par_iter())
+        .map(|(&(path, name, rule), &counter)| {
+            let caps = rule.pattern.captures(name).ok_or((path.to_path_buf(), RenamerError::InvalidPattern))?;
+            let new_name = rule
+                .template
+                .render_with(&caps, counter)
+                .map_err(|e| (path.to_path_buf(), e))?;
+            let mut plan = PlannedRename::new(path, path.with_file_name(enforce_extension(new_name, name)));
+            plan.warn = check_warning(name, &rule.pattern);
+            plan.rule = Some(rule.name.clone());
+            Ok(plan)
+        })
+        .collect();
+
+    let mut plan = Vec::with_capacity(results.len());
+    let mut errors = Vec::new();
+    for result in results {
+        match result {
+            Ok(p) => plan.push(p),
+            Err(e) => errors.push(e),
+        }
+    }
+    (plan, errors)
+}
+
+/// Sorts `paths` in the order given by `key`.
+///
+/// The plan is built in this order, so its log output, `{n}` numbering and conflict
+/// suffixes are the same from run to run.
+///
+/// # Examples
+///
+/// ```
+/// # use std::path::PathBuf;
+/// # use renamer::plan::{sort_paths, SortKey};
+/// let mut paths = vec![PathBuf::from("ep10.mkv"), PathBuf::from("ep9.mkv")];
+/// sort_paths(&mut paths, SortKey::Natural);
+/// assert_eq!(paths, vec![PathBuf::from("ep9.mkv"), PathBuf::from("ep10.mkv")]);
+/// ```
+pub fn sort_paths(paths: &mut Vec<PathBuf>, key: SortKey) {
+    let order = sorted_indices(&paths.iter().map(PathBuf::as_path).collect::<Vec<_>>(), key);
+    let mut slots: Vec<Option<PathBuf>> = paths.drain(..).map(Some).collect();
+    paths.extend(order.into_iter().filter_map(|i| slots[i].take()));
+}
+
+/// Returns the indices of `paths` in the order given by `key`.
+///
+/// Ties (equal sizes or modification times) are broken by natural path order, so the
+/// result is deterministic.
+pub fn sorted_indices(paths: &[&Path], key: SortKey) -> Vec<usize> {
+    let names: Vec<String> = paths.iter().map(|p| p.to_string_lossy().into_owned()).collect();
+    let stamps: Vec<Option<FileStamp>> = match key {
+        SortKey::Mtime | SortKey::Size => paths.iter().map(|p| FileStamp::of(p).ok()).collect(),
+        SortKey::Name | SortKey::Natural => Vec::new(),
+    };
+    let mut indices: Vec<usize> = (0..paths.len()).collect();
+    indices.sort_by(|&a, &b| {
+        let primary = match key {
+            SortKey::Name => paths[a].cmp(paths[b]),
+            SortKey::Natural => Ordering::Equal,
+            SortKey::Mtime => stamps[a].map(|s| s.mtime).cmp(&stamps[b].map(|s| s.mtime)),
+            SortKey::Size => stamps[a].map(|s| s.size).cmp(&stamps[b].map(|s| s.size)),
+        };
+        primary.then_with(|| natural_cmp(&names[a], &names[b]))
+    });
+    indices
+}
+
+/// Compares two strings in natural order.
+///
+/// Runs of ASCII digits are compared by numeric value and other characters
+/// case-insensitively, so `"Episode 2"` sorts before `"episode 10"`. Strings that only
+/// differ in case or leading zeros fall back to byte-wise order.
+///
+/// # Examples
+///
+/// ```
+/// # use std::cmp::Ordering;
+/// # use renamer::plan::natural_cmp;
+/// assert_eq!(natural_cmp("IMG_9.jpg", "IMG_10.jpg"), Ordering::Less);
+/// assert_eq!(natural_cmp("b", "A"), Ordering::Greater);
+/// ```
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut x, mut y) = (a, b);
+    loop {
+        let (Some(cx), Some(cy)) = (x.chars().next(), y.chars().next()) else {
+            return x.len().cmp(&y.len()).then_with(|| a.cmp(b));
+        };
+        if cx.is_ascii_digit() && cy.is_ascii_digit() {
+            let dx = x.find(|c: char| !c.is_ascii_digit()).unwrap_or(x.len());
+            let dy = y.find(|c: char| !c.is_ascii_digit()).unwrap_or(y.len());
+            let nx = x[..dx].trim_start_matches('0');
+            let ny = y[..dy].trim_start_matches('0');
+            let ord = nx.len().cmp(&ny.len()).then_with(|| nx.cmp(ny));
+            if ord != Ordering::Equal {
+                return ord;
+            }
+            x = &x[dx..];
+            y = &y[dy..];
+        } else {
+            let ord = cx.to_lowercase().cmp(cy.to_lowercase());
+            if ord != Ordering::Equal {
+                return ord;
+            }
+            x = &x[cx.len_utf8()..];
+            y = &y[cy.len_utf8()..];
+        }
+    }
+}
+
+/// What to do when a rename's target is already taken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictPolicy {
+    /// Leave the conflicting file untouched.
+    Skip,
+    /// Replace the existing file. Overwritten files cannot be restored by `undo`.
+    Overwrite,
+    /// Append a numbered suffix such as ` (2)` to the new name.
+    Suffix,
+    /// Abort the run before any file is touched.
+    #[default]
+    Abort,
+}
+
+/// A problem found in a rename plan that would lose data if executed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Conflict {
+    /// Several files would be renamed to the same target.
+    DuplicateTarget { target: PathBuf, sources: Vec<PathBuf> },
+    /// The target already exists on disk and would be overwritten.
+    ExistingTarget { source: PathBuf, target: PathBuf },
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Conflict::DuplicateTarget { target, sources } => {
+                write!(f, "{:?} is the target of {} files:", target, sources.len())?;
+                for source in sources {
+                    write!(f, "\n    {:?}", source)?;
+                }
+                Ok(())
+            }
+            Conflict::ExistingTarget { source, target } => {
+                write!(f, "{:?} would overwrite existing file {:?}", source, target)
+            }
+        }
+    }
+}
+
+/// Checks a whole plan for conflicts.
+///
+/// Reports every target shared by more than one planned rename, and every target
+/// that already exists on disk. A target that is itself the source of another planned
+/// rename is not a conflict: the executor moves that file away first (see
+/// [`crate::executor::order_renames`]). Renames whose source and target are the same
+/// file (for example a case-only change on a case-insensitive file system) are not
+/// conflicts either.
+///
+/// Conflicts are returned grouped by kind (duplicates first) and sorted by target.
+///
+/// # Examples
+///
+/// ```
+/// # use std::path::PathBuf;
+/// # use renamer::PlannedRename;
+/// # use renamer::plan::{find_conflicts, Conflict};
+/// let plan = vec![
+///     PlannedRename::new("/none/a.mkv", "/none/x.mkv"),
+///     PlannedRename::new("/none/b.mkv", "/none/x.mkv"),
+/// ];
+/// let conflicts = find_conflicts(&plan);
+/// assert_eq!(conflicts, vec![Conflict::DuplicateTarget {
+///     target: PathBuf::from("/none/x.mkv"),
+///     sources: vec![PathBuf::from("/none/a.mkv"), PathBuf::from("/none/b.mkv")],
+/// }]);
+/// ```
+pub fn find_conflicts(plan: &[PlannedRename]) -> Vec<Conflict> {
+    let mut by_target: BTreeMap<&Path, Vec<&PlannedRename>> = BTreeMap::new();
+    for rename in plan.iter().filter(|p| is_active(p)) {
+        by_target.entry(&rename.new_path).or_default().push(rename);
+    }
+    let moved_away = moved_away(plan);
+
+    let mut duplicates = Vec::new();
+    let mut existing = Vec::new();
+    let mut duplicated: HashSet<&Path> = HashSet::new();
+    for (target, sources) in &by_target {
+        if sources.len() > 1 {
+            let mut sources: Vec<PathBuf> = sources.iter().map(|s| s.old_path.clone()).collect();
+            sources.sort();
+            duplicated.insert(target);
+            duplicates.push(Conflict::DuplicateTarget { target: target.to_path_buf(), sources });
+        }
+    }
+    for (target, sources) in &by_target {
+        if duplicated.contains(target) || moved_away.contains(target) {
+            continue;
+        }
+        let rename = sources[0];
+        if rename.action != RenameAction::Overwrite && is_occupied(&rename.old_path, target) {
+            existing.push(Conflict::ExistingTarget {
+                source: rename.old_path.clone(),
+                target: target.to_path_buf(),
+            });
+        }
+    }
+    duplicates.extend(existing);
+    duplicates
+}
+
+/// Resolves the conflicts of a plan according to `policy`.
+///
+/// Renames are considered in plan order, so when several files claim the same
+/// target the first one keeps it:
+///
+/// - [`ConflictPolicy::Skip`] marks every later claimant, and every rename whose target
+///   exists on disk, as [`RenameAction::Skip`]. Skipping a file keeps its name occupied,
+///   which may in turn cause other renames to be skipped.
+/// - [`ConflictPolicy::Suffix`] gives those renames the first free name of the form
+///   `name (2).ext`, `name (3).ext`, ...
+/// - [`ConflictPolicy::Overwrite`] marks renames onto existing files as
+///   [`RenameAction::Overwrite`]; duplicate targets within the plan stay unresolved.
+/// - [`ConflictPolicy::Abort`] leaves the plan untouched.
+///
+/// Returns the conflicts that remain; a non-empty result means the run must abort.
+///
+/// # Examples
+///
+/// ```
+/// # use renamer::PlannedRename;
+/// # use renamer::plan::{resolve_conflicts, ConflictPolicy};
+/// let mut plan = vec![
+///     PlannedRename::new("/none/a.mkv", "/none/x.mkv"),
+///     PlannedRename::new("/none/b.mkv", "/none/x.mkv"),
+/// ];
+/// assert!(resolve_conflicts(&mut plan, ConflictPolicy::Suffix).is_empty());
+/// assert_eq!(plan[1].new_path, std::path::PathBuf::from("/none/x (2).mkv"));
+/// ```
+pub fn resolve_conflicts(plan: &mut [PlannedRename], policy: ConflictPolicy) -> Vec<Conflict> {
+    if policy != ConflictPolicy::Abort {
+        loop {
+            let moved_away: HashSet<PathBuf> = moved_away(plan).into_iter().map(Path::to_path_buf).collect();
+            let mut claimed: HashSet<PathBuf> = HashSet::new();
+            let mut newly_skipped = false;
+            for rename in plan.iter_mut().filter(|p| is_active(p)) {
+                let taken = |target: &Path| {
+                    claimed.contains(target)
+                        || (!moved_away.contains(target) && is_occupied(&rename.old_path, target))
+                };
+                let duplicate = claimed.contains(&rename.new_path);
+                if duplicate || taken(&rename.new_path) {
+                    match policy {
+                        ConflictPolicy::Skip => {
+                            let reason = if duplicate {
+                                format!("target {:?} is claimed by another file", rename.new_path)
+                            } else {
+                                format!("target {:?} already exists", rename.new_path)
+                            };
+                            rename.action = RenameAction::Skip(reason);
+                            newly_skipped = true;
+                            continue;
+                        }
+                        ConflictPolicy::Suffix => {
+                            let free = (2..)
+                                .map(|n| with_suffix(&rename.new_path, n))
+                                .find(|candidate| !taken(candidate))
+                                .expect("a free suffixed name exists");
+                            rename.new_path = free;
+                        }
+                        ConflictPolicy::Overwrite if !duplicate => rename.action = RenameAction::Overwrite,
+                        ConflictPolicy::Overwrite | ConflictPolicy::Abort => {}
+                    }
+                }
+                claimed.insert(rename.new_path.clone());
+            }
+            if !newly_skipped {
+                break;
+            }
+        }
+    }
+    find_conflicts(plan)
+}
+
+/// True for renames that will actually move a file.
+fn is_active(rename: &PlannedRename) -> bool {
+    rename.old_path != rename.new_path && !rename.action.is_skip()
+}
+
+/// The sources of all renames that will move a file away.
+fn moved_away(plan: &[PlannedRename]) -> HashSet<&Path> {
+    plan.iter().filter(|p| is_active(p)).map(|p| p.old_path.as_path()).collect()
+}
+
+/// True if `target` exists on disk and is not the same file as `source`.
+fn is_occupied(source: &Path, target: &Path) -> bool {
+    target.exists() && !same_file::is_same_file(source, target).unwrap_or(false)
+}
+
+/// Inserts ` (n)` between the stem and the extension of `path`.
+fn with_suffix(path: &Path, n: usize) -> PathBuf {
+    let stem = path.file_stem().map(|s| s.to_string_lossy()).unwrap_or_default();
+    match path.extension() {
+        Some(ext) => path.with_file_name(format!("{} ({}).{}", stem, n, ext.to_string_lossy())),
+        None => path.with_file_name(format!("{} ({})", stem, n)),
+    }
+}
+
+/// Formats conflicts as a human-readable report, grouped by kind.
+pub fn conflict_report(conflicts: &[Conflict]) -> String {
+    let duplicates: Vec<String> = conflicts
+        .iter()
+        .filter(|c| matches!(c, Conflict::DuplicateTarget { .. }))
+        .map(|c| format!("  - {}", c))
+        .collect();
+    let existing: Vec<String> = conflicts
+        .iter()
+        .filter(|c| matches!(c, Conflict::ExistingTarget { .. }))
+        .map(|c| format!("  - {}", c))
+        .collect();
+    let mut sections = Vec::new();
+    if !duplicates.is_empty() {
+        sections.push(format!("Duplicate targets ({}):\n{}", duplicates.len(), duplicates.join("\n")));
+    }
+    if !existing.is_empty() {
+        sections.push(format!("Existing files would be overwritten ({}):\n{}", existing.len(), existing.join("\n")));
+    }
+    sections.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_natural_cmp() {
+        let mut names = vec!["ep10.mkv", "Ep2.mkv", "ep1.mkv", "ep01.mkv", "ep1a.mkv", "ep"];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(names, vec!["ep", "ep01.mkv", "ep1.mkv", "ep1a.mkv", "Ep2.mkv", "ep10.mkv"]);
+    }
+
+    #[test]
+    fn test_sorted_indices_by_size() {
+        let dir = tempdir().unwrap();
+        let big = dir.path().join("a.bin");
+        let small = dir.path().join("b.bin");
+        let tie = dir.path().join("c.bin");
+        fs::write(&big, "xxxx").unwrap();
+        fs::write(&small, "x").unwrap();
+        fs::write(&tie, "x").unwrap();
+        let paths = vec![tie.as_path(), big.as_path(), small.as_path()];
+        assert_eq!(sorted_indices(&paths, SortKey::Size), vec![2, 0, 1]);
+        assert_eq!(sorted_indices(&paths, SortKey::Name), vec![1, 2, 0]);
+    }
+
+    #[test]
+    fn test_sort_paths() {
+        let mut paths: Vec<PathBuf> = ["b/ep2", "a/ep10", "a/Ep9", "b/ep10"].iter().map(PathBuf::from).collect();
+        sort_paths(&mut paths, SortKey::Natural);
+        assert_eq!(paths, vec![
+            PathBuf::from("a/Ep9"),
+            PathBuf::from("a/ep10"),
+            PathBuf::from("b/ep2"),
+            PathBuf::from("b/ep10"),
+        ]);
+        sort_paths(&mut paths, SortKey::Name);
+        assert_eq!(paths[0], PathBuf::from("a/Ep9"));
+        assert_eq!(paths[1], PathBuf::from("a/ep10"));
+        assert_eq!(paths[2], PathBuf::from("b/ep10"));
+    }
+
+    fn rule(spec: &str) -> Vec<Rule> {
+        vec![Rule::compile(&spec.parse().unwrap(), 0).unwrap()]
+    }
+
+    #[test]
+    fn test_build_plan_counter_is_deterministic() {
+        let rules = rule(r"^IMG_(?P<id>\d+)=>Photo {n:03}");
+        let sequence = Sequence { start: 10, step: 5, sort: SortKey::Natural };
+        let files: Vec<PathBuf> = ["IMG_100.jpg", "notes.txt", "IMG_9.jpg", "IMG_20.jpg"]
+            .iter()
+            .map(|n| Path::new("/none").join(n))
+            .collect();
+        let (plan, errors) = build_plan(&files, &rules, &sequence);
+        assert!(errors.is_empty());
+        let names: Vec<_> = plan.iter().map(|p| p.new_path.file_name().unwrap().to_owned()).collect();
+        assert_eq!(names, vec!["Photo 020.jpg", "Photo 010.jpg", "Photo 015.jpg"]);
+
+        let mut reversed = files.clone();
+        reversed.reverse();
+        let (plan_rev, _) = build_plan(&reversed, &rules, &sequence);
+        assert_eq!(plan_rev.iter().rev().cloned().collect::<Vec<_>>(), plan);
+    }
+
+    #[test]
+    fn test_build_plan_reports_render_errors() {
+        let rules = rule(r"^(?P<ep>\w+)=>E{ep-1}");
+        let files = vec![PathBuf::from("/none/12.mkv"), PathBuf::from("/none/abc.mkv")];
+        let (plan, errors) = build_plan(&files, &rules, &Sequence::default());
+        assert_eq!(plan.len(), 1);
+        assert_eq!(plan[0].new_path, PathBuf::from("/none/E11.mkv"));
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(errors[0].1, RenamerError::NotNumeric { .. }));
+    }
+
+    #[test]
+    fn test_build_plan_first_matching_rule_wins() {
+        let rules: Vec<Rule> = [
+            r"S(?P<season>\d+)E(?P<episode>\d+)=>S{season:02}E{episode:02}",
+            r"(?P<season>\d+)x(?P<episode>\d+)=>S{season:02}E{episode:02}",
+            r"(?i)ep\.? ?(?P<episode>\d+)=>S01E{episode:02}",
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, s)| Rule::compile(&s.parse().unwrap(), i).unwrap())
+        .collect();
+        let files: Vec<PathBuf> = ["S1E2 Ep 9.mkv", "1x03.mkv", "Ep 04.mkv", "extras.mkv"]
+            .iter()
+            .map(|n| Path::new("/d").join(n))
+            .collect();
+        let (plan, errors) = build_plan(&files, &rules, &Sequence::default());
+        assert!(errors.is_empty());
+        let result: Vec<(&str, &str)> = plan
+            .iter()
+            .map(|p| (p.new_path.file_name().unwrap().to_str().unwrap(), p.rule.as_deref().unwrap()))
+            .collect();
+        assert_eq!(result, vec![("S01E02.mkv", "#1"), ("S01E03.mkv", "#2"), ("S01E04.mkv", "#3")]);
+    }
+
+    #[test]
+    fn test_no_conflicts() {
+        let dir = tempdir().unwrap();
+        let a = dir.path().join("a.mkv");
+        fs::write(&a, "a").unwrap();
+        let plan = vec![PlannedRename::new(&a, dir.path().join("b.mkv"))];
+        assert!(find_conflicts(&plan).is_empty());
+    }
+
+    #[test]
+    fn test_existing_target() {
+        let dir = tempdir().unwrap();
+        let a = dir.path().join("a.mkv");
+        let b = dir.path().join("b.mkv");
+        fs::write(&a, "a").unwrap();
+        fs::write(&b, "b").unwrap();
+        let conflicts = find_conflicts(&[PlannedRename::new(&a, &b)]);
+        assert_eq!(conflicts, vec![Conflict::ExistingTarget { source: a, target: b }]);
+    }
+
+    #[test]
+    fn test_unchanged_name_is_not_a_conflict() {
+        let dir = tempdir().unwrap();
+        let a = dir.path().join("a.mkv");
+        fs::write(&a, "a").unwrap();
+        assert!(find_conflicts(&[PlannedRename::new(&a, &a)]).is_empty());
+    }
+
+    #[test]
+    fn test_duplicates_reported_before_existing() {
+        let dir = tempdir().unwrap();
+        let a = dir.path().join("a.mkv");
+        let b = dir.path().join("b.mkv");
+        let c = dir.path().join("c.mkv");
+        let d = dir.path().join("d.mkv");
+        let e = dir.path().join("e.mkv");
+        let x = dir.path().join("x.mkv");
+        let y = dir.path().join("y.mkv");
+        for p in [&a, &b, &c, &d, &e, &x] {
+            fs::write(p, "data").unwrap();
+        }
+        let plan = vec![PlannedRename::new(&c, &x), PlannedRename::new(&b, &a), PlannedRename::new(&d, &y), PlannedRename::new(&e, &y)];
+        let conflicts = find_conflicts(&plan);
+        assert_eq!(conflicts.len(), 3);
+        assert!(matches!(&conflicts[0], Conflict::DuplicateTarget { sources, .. } if sources.len() == 2));
+        assert!(matches!(&conflicts[1], Conflict::ExistingTarget { target, .. } if *target == a));
+        assert!(matches!(&conflicts[2], Conflict::ExistingTarget { target, .. } if *target == x));
+
+        let report = conflict_report(&conflicts);
+        assert!(report.starts_with("Duplicate targets (1):"));
+        assert!(report.contains("Existing files would be overwritten (2):"));
+    }
+
+    #[test]
+    fn test_target_moved_away_is_not_a_conflict() {
+        let dir = tempdir().unwrap();
+        let a = dir.path().join("a.mkv");
+        let b = dir.path().join("b.mkv");
+        fs::write(&a, "a").unwrap();
+        fs::write(&b, "b").unwrap();
+        assert!(find_conflicts(&[PlannedRename::new(&a, &b), PlannedRename::new(&b, &a)]).is_empty());
+        assert!(find_conflicts(&[PlannedRename::new(&a, &b), PlannedRename::new(&b, dir.path().join("c.mkv"))]).is_empty());
+    }
+
+    #[test]
+    fn test_resolve_abort_keeps_conflicts() {
+        let dir = tempdir().unwrap();
+        let a = dir.path().join("a.mkv");
+        let b = dir.path().join("b.mkv");
+        fs::write(&a, "a").unwrap();
+        fs::write(&b, "b").unwrap();
+        let mut plan = vec![PlannedRename::new(&a, &b)];
+        assert_eq!(resolve_conflicts(&mut plan, ConflictPolicy::Abort).len(), 1);
+        assert_eq!(plan[0].action, RenameAction::Rename);
+    }
+
+    #[test]
+    fn test_resolve_skip_cascades() {
+        let dir = tempdir().unwrap();
+        let a = dir.path().join("a.mkv");
+        let b = dir.path().join("b.mkv");
+        let x = dir.path().join("x.mkv");
+        for p in [&a, &b, &x] {
+            fs::write(p, "data").unwrap();
+        }
+        // b→x is skipped because x exists, so b stays in place and a→b must be skipped too.
+        let mut plan = vec![PlannedRename::new(&a, &b), PlannedRename::new(&b, &x)];
+        assert!(resolve_conflicts(&mut plan, ConflictPolicy::Skip).is_empty());
+        assert!(plan[0].action.is_skip());
+        assert!(plan[1].action.is_skip());
+    }
+
+    #[test]
+    fn test_resolve_suffix() {
+        let dir = tempdir().unwrap();
+        let a = dir.path().join("a.mkv");
+        let b = dir.path().join("b.mkv");
+        let c = dir.path().join("c.mkv");
+        let x = dir.path().join("x.mkv");
+        for p in [&a, &b, &c, &x, &dir.path().join("x (2).mkv")] {
+            fs::write(p, "data").unwrap();
+        }
+        let mut plan = vec![PlannedRename::new(&a, &x), PlannedRename::new(&b, &x), PlannedRename::new(&c, dir.path().join("y.mkv"))];
+        assert!(resolve_conflicts(&mut plan, ConflictPolicy::Suffix).is_empty());
+        assert_eq!(plan[0].new_path, dir.path().join("x (3).mkv"));
+        assert_eq!(plan[1].new_path, dir.path().join("x (4).mkv"));
+        assert_eq!(plan[2].new_path, dir.path().join("y.mkv"));
+    }
+
+    #[test]
+    fn test_resolve_overwrite() {
+        let dir = tempdir().unwrap();
+        let a = dir.path().join("a.mkv");
+        let b = dir.path().join("b.mkv");
+        let c = dir.path().join("c.mkv");
+        let x = dir.path().join("x.mkv");
+        for p in [&a, &b, &c, &x] {
+            fs::write(p, "data").unwrap();
+        }
+        let mut plan = vec![PlannedRename::new(&a, &x)];
+        assert!(resolve_conflicts(&mut plan, ConflictPolicy::Overwrite).is_empty());
+        assert_eq!(plan[0].action, RenameAction::Overwrite);
+
+        // Two files renamed to the same target cannot both be kept.
+        let y = dir.path().join("y.mkv");
+        let mut plan = vec![PlannedRename::new(&b, &y), PlannedRename::new(&c, &y)];
+        assert_eq!(resolve_conflicts(&mut plan, ConflictPolicy::Overwrite).len(), 1);
+    }
+}
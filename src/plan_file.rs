@@ -0,0 +1,202 @@
+//! Plan file module for the renamer tool.
+//! A computed rename plan can be saved with `--plan-out` so it can be reviewed or
+//! hand-edited, and executed later with `renamer apply --plan`. Applying refuses to run
+//! if a source file was moved or modified since the plan was made.
+//!
+//! The plan file is a pretty-printed JSON [`PlanFile`].
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::{anyhow, Result};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use crate::file_ops::{absolute, FileStamp};
+use crate::plan::Conflict;
+use crate::renamer::{PlannedRename, RenameAction};
+
+/// Version of the plan file format written by this build.
+pub const PLAN_VERSION: u32 = 1;
+
+/// A saved rename plan.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlanFile {
+    /// Format version, see [`PLAN_VERSION`].
+    pub version: u32,
+    /// Local time at which the plan was made (RFC 3339).
+    pub created: String,
+    /// Conflicts left unresolved when the plan was made; such a plan cannot be applied
+    /// until they are fixed by hand.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conflicts: Vec<String>,
+    pub renames: Vec<PlanEntry>,
+}
+
+/// One rename of a saved plan.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlanEntry {
+    pub old_path: PathBuf,
+    pub new_path: PathBuf,
+    /// True if a warning condition was met (e.g., season or episode equals "0").
+    #[serde(default)]
+    pub warn: bool,
+    /// What the executor will do with this file.
+    #[serde(default)]
+    pub action: RenameAction,
+    /// Name of the rule that matched the file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule: Option<String>,
+    /// Stamp of the source file when the plan was made. Entries added by hand may omit it.
+    #[serde(default)]
+    pub stamp: Option<FileStamp>,
+}
+
+impl PlanFile {
+    /// Captures `plan` and its unresolved `conflicts`, stamping every source file.
+    ///
+    /// Paths are stored as absolute paths so the plan can be applied from any directory.
+    pub fn new(plan: &[PlannedRename], conflicts: &[Conflict]) -> PlanFile {
+        let renames = plan
+            .iter()
+            .map(|p| PlanEntry {
+                old_path: absolute(&p.old_path),
+                new_path: absolute(&p.new_path),
+                warn: p.warn,
+                action: p.action.clone(),
+                rule: p.rule.clone(),
+                stamp: FileStamp::of(&p.old_path).ok(),
+            })
+            .collect();
+        PlanFile {
+            version: PLAN_VERSION,
+            created: Local::now().to_rfc3339(),
+            conflicts: conflicts.iter().map(|c| c.to_string()).collect(),
+            renames,
+        }
+    }
+
+    /// Writes the plan to `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json + "\n").map_err(|e| anyhow!("Failed to write plan {:?}: {}", path, e))
+    }
+
+    /// Reads a plan from `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or parsed, or was written by an
+    /// incompatible version.
+    pub fn load(path: &Path) -> Result<PlanFile> {
+        let json = fs::read_to_string(path).map_err(|e| anyhow!("Failed to read plan {:?}: {}", path, e))?;
+        let plan: PlanFile = serde_json::from_str(&json).map_err(|e| anyhow!("Failed to parse plan {:?}: {}", path, e))?;
+        if plan.version != PLAN_VERSION {
+            return Err(anyhow!(
+                "Plan {:?} has format version {}, this build reads version {}",
+                path, plan.version, PLAN_VERSION
+            ));
+        }
+        Ok(plan)
+    }
+
+    /// Returns the source files that no longer match their stamp, with the reason.
+    ///
+    /// Skipped entries are not checked, nor are entries without a stamp.
+    pub fn changed_sources(&self) -> Vec<(PathBuf, String)> {
+        self.renames
+            .iter()
+            .filter(|e| !e.action.is_skip())
+            .filter_map(|e| {
+                let expected = e.stamp?;
+                let reason = match FileStamp::of(&e.old_path) {
+                    Err(_) => "source is missing",
+                    Ok(stamp) if stamp != expected => "source was modified",
+                    Ok(_) => return None,
+                };
+                Some((e.old_path.clone(), reason.to_string()))
+            })
+            .collect()
+    }
+
+    /// Returns the plan as planned renames, in order.
+    pub fn renames(&self) -> Vec<PlannedRename> {
+        self.renames
+            .iter()
+            .map(|e| PlannedRename {
+                old_path: e.old_path.clone(),
+                new_path: e.new_path.clone(),
+                warn: e.warn,
+                action: e.action.clone(),
+                rule: e.rule.clone(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_save_and_load_round_trip() {
+        let dir = tempdir().unwrap();
+        let a = dir.path().join("a.txt");
+        let b = dir.path().join("b.txt");
+        fs::write(&a, "a").unwrap();
+        fs::write(&b, "b").unwrap();
+        let mut skipped = PlannedRename::new(&b, dir.path().join("c.txt"));
+        skipped.action = RenameAction::Skip("target exists".to_string());
+        let mut plan = vec![PlannedRename::new(&a, dir.path().join("x.txt")), skipped];
+        plan[0].warn = true;
+        plan[0].rule = Some("tv".to_string());
+
+        let path = dir.path().join("plan.json");
+        PlanFile::new(&plan, &[]).save(&path).unwrap();
+        let loaded = PlanFile::load(&path).unwrap();
+        assert_eq!(loaded.renames(), plan);
+        assert!(loaded.conflicts.is_empty());
+        assert!(loaded.changed_sources().is_empty());
+    }
+
+    #[test]
+    fn test_changed_sources() {
+        let dir = tempdir().unwrap();
+        let a = dir.path().join("a.txt");
+        let b = dir.path().join("b.txt");
+        fs::write(&a, "a").unwrap();
+        fs::write(&b, "b").unwrap();
+        let plan = PlanFile::new(
+            &[PlannedRename::new(&a, dir.path().join("x")), PlannedRename::new(&b, dir.path().join("y"))],
+            &[],
+        );
+        fs::write(&a, "changed").unwrap();
+        fs::remove_file(&b).unwrap();
+        let changed = plan.changed_sources();
+        assert_eq!(changed, vec![
+            (a, "source was modified".to_string()),
+            (b, "source is missing".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_hand_edited_plan() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("plan.json");
+        fs::write(&path, r#"{"version": 1, "created": "", "renames": [
+            {"old_path": "/none/a", "new_path": "/none/b"},
+            {"old_path": "/none/c", "new_path": "/none/d", "action": {"skip": "kept"}}
+        ]}"#).unwrap();
+        let plan = PlanFile::load(&path).unwrap();
+        assert!(plan.changed_sources().is_empty());
+        let renames = plan.renames();
+        assert_eq!(renames[0], PlannedRename::new("/none/a", "/none/b"));
+        assert_eq!(renames[1].action, RenameAction::Skip("kept".to_string()));
+
+        fs::write(&path, r#"{"version": 99, "created": "", "renames": []}"#).unwrap();
+        assert!(PlanFile::load(&path).is_err());
+    }
+}
@@ -0,0 +1,144 @@
+//! Preset module for the renamer tool.
+//! Presets are named current-name patterns with a default new-name template for common
+//! naming schemes, selected with `--preset` (or `preset = "..."` in a config file) so
+//! the regex does not have to be written by hand. Config files can override a built-in
+//! preset or add their own in a `[presets.NAME]` table.
+
+use std::collections::BTreeMap;
+use crate::rule::RuleSpec;
+
+/// A built-in preset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Preset {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Regex with named groups matched against file names.
+    pub pattern: &'static str,
+    /// Default new-name template.
+    pub template: &'static str,
+    /// A file name the preset matches, and the name it is renamed to.
+    pub example: (&'static str, &'static str),
+}
+
+/// The built-in presets.
+pub const PRESETS: [Preset; 6] = [
+    Preset {
+        name: "tv-sxxeyy",
+        description: "TV episodes numbered S01E02",
+        pattern: r"^(?:(?P<title>.+?)[ ._-]+)?[Ss](?P<season>\d{1,2})[Ee](?P<episode>\d{1,3})",
+        template: "[{title|replace:.: |replace:_: |trim} - ]S{season:02}E{episode:02}",
+        example: ("The.Office.S02E05.720p.mkv", "The Office - S02E05.mkv"),
+    },
+    Preset {
+        name: "tv-1x02",
+        description: "TV episodes numbered 1x02",
+        pattern: r"^(?:(?P<title>.+?)[ ._-]+)?(?P<season>\d{1,2})x(?P<episode>\d{2,3})",
+        template: "[{title|replace:.: |replace:_: |trim} - ]S{season:02}E{episode:02}",
+        example: ("Doctor_Who_3x07_42.avi", "Doctor Who - S03E07.avi"),
+    },
+    Preset {
+        name: "anime-bracket",
+        description: "Fansub releases named [Group] Title - 05",
+        pattern: r"^\[(?P<group>[^\]]+)\][ _]*(?P<title>.+?)[ _]+-[ _]+(?P<episode>\d{1,4})",
+        template: "{title} - E{episode:02}",
+        example: ("[SubsPlease] Frieren - 5 (1080p) [ABCD1234].mkv", "Frieren - E05.mkv"),
+    },
+    Preset {
+        name: "music-artist-album-track",
+        description: "Music named Artist - Album - Track[ - Title]",
+        pattern: r"^(?P<artist>.+?) *- *(?P<album>.+?) *- *(?P<track>\d{1,3})(?:(?: *- *| *\. +)(?P<title>.+?))?(?:\.[^.]+)?$",
+        template: "{artist} - {album} - {track:02}[ - {title}]",
+        example: ("Daft Punk-Discovery-3-Digital Love.flac", "Daft Punk - Discovery - 03 - Digital Love.flac"),
+    },
+    Preset {
+        name: "photo-img-date",
+        description: "Camera and phone photos named IMG_20240131_...",
+        pattern: r"^(?:IMG|PXL|VID)[_-](?P<year>\d{4})(?P<month>\d{2})(?P<day>\d{2})",
+        template: "{year}-{month}-{day} {n:03}",
+        example: ("IMG_20240131_093000.jpg", "2024-01-31 001.jpg"),
+    },
+    Preset {
+        name: "date-iso",
+        description: "Rewrites the first 20240131-style date in a name as 2024-01-31",
+        pattern: r"^(?P<before>.*?)(?P<year>(?:19|20)\d{2})[-_.]?(?P<month>0[1-9]|1[0-2])[-_.]?(?P<day>0[1-9]|[12]\d|3[01])(?P<after>.*?)(?:\.[^.]+)?$",
+        template: "{before}{year}-{month}-{day}{after}",
+        example: ("Scan_20240131_invoice.pdf", "Scan_2024-01-31_invoice.pdf"),
+    },
+];
+
+impl Preset {
+    /// Returns the built-in preset called `name`.
+    pub fn find(name: &str) -> Option<&'static Preset> {
+        PRESETS.iter().find(|preset| preset.name == name)
+    }
+
+    /// The preset as a rule named after it.
+    pub fn spec(&self) -> RuleSpec {
+        RuleSpec { name: Some(self.name.to_string()), pattern: self.pattern.to_string(), template: self.template.to_string() }
+    }
+}
+
+/// Returns the preset called `name` as a rule, looking in the presets defined in config
+/// files (`custom`) before the built-in ones.
+///
+/// # Examples
+///
+/// ```
+/// # use std::collections::BTreeMap;
+/// # use renamer::preset::resolve;
+/// let spec = resolve("tv-1x02", &BTreeMap::new()).unwrap();
+/// assert_eq!(spec.template, "[{title|replace:.: |replace:_: |trim} - ]S{season:02}E{episode:02}");
+/// let custom = BTreeMap::from([("tv-1x02".to_string(), r"(?P<s>\d)x(?P<e>\d+)=>S{s}E{e}".parse().unwrap())]);
+/// assert_eq!(resolve("tv-1x02", &custom).unwrap().template, "S{s}E{e}");
+/// assert!(resolve("nope", &custom).is_none());
+/// ```
+pub fn resolve(name: &str, custom: &BTreeMap<String, RuleSpec>) -> Option<RuleSpec> {
+    let spec = match custom.get(name) {
+        Some(spec) => spec.clone(),
+        None => Preset::find(name)?.spec(),
+    };
+    Some(RuleSpec { name: Some(name.to_string()), ..spec })
+}
+
+/// Returns the names of every preset, built-in or defined in config files, sorted.
+pub fn names(custom: &BTreeMap<String, RuleSpec>) -> Vec<&str> {
+    let mut names: Vec<&str> = PRESETS.iter().map(|p| p.name).chain(custom.keys().map(String::as_str)).collect();
+    names.sort_unstable();
+    names.dedup();
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::{Path, PathBuf};
+    use crate::plan::{build_plan, Sequence};
+    use crate::rule::Rule;
+
+    #[test]
+    fn test_presets_rename_their_examples() {
+        for preset in PRESETS {
+            let rule = Rule::compile(&preset.spec(), 0).unwrap();
+            let (old, new) = preset.example;
+            let (plan, errors) = build_plan(&[Path::new("/d").join(old)], &[rule], &Sequence::default());
+            assert!(errors.is_empty(), "{}: {:?}", preset.name, errors);
+            assert_eq!(plan.len(), 1, "{} does not match {}", preset.name, old);
+            assert_eq!(plan[0].new_path, PathBuf::from("/d").join(new), "{}", preset.name);
+        }
+    }
+
+    #[test]
+    fn test_preset_variants() {
+        let render = |name: &str, file: &str| {
+            let rule = Rule::compile(&Preset::find(name).unwrap().spec(), 0).unwrap();
+            let (plan, _) = build_plan(&[PathBuf::from(file)], &[rule], &Sequence::default());
+            plan.first().map(|p| p.new_path.to_string_lossy().into_owned())
+        };
+        assert_eq!(render("tv-sxxeyy", "s1e2.mkv").as_deref(), Some("S01E02.mkv"));
+        assert_eq!(render("music-artist-album-track", "Artist - Album - 7.mp3").as_deref(), Some("Artist - Album - 07.mp3"));
+        assert_eq!(render("music-artist-album-track", "Artist - Album - 07. Song.mp3").as_deref(), Some("Artist - Album - 07 - Song.mp3"));
+        assert_eq!(render("date-iso", "2023.12.24.jpg").as_deref(), Some("2023-12-24.jpg"));
+        assert_eq!(render("photo-img-date", "DSC_0001.jpg"), None);
+        assert_eq!(names(&BTreeMap::from([("mine".to_string(), "a=>b".parse().unwrap())])).len(), 7);
+    }
+}
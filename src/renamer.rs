@@ -3,19 +3,59 @@
 //! The renamer is designed to work with any file type and naming pattern using regex capture groups.
 
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
-use crate::error::RenamerError; 
+use crate::error::RenamerError;
+use crate::template::Template;
 
 /// A planned renaming operation.
 ///
-/// Stores the original and new file paths, and a flag indicating if a warning
-/// should be triggered due to specific captured values being "0".
-#[derive(Debug)]
+/// Stores the original and new file paths, a flag indicating if a warning
+/// should be triggered due to specific captured values being "0", and the action
+/// chosen for the file once conflicts have been resolved.
+#[derive(Debug, Clone, PartialEq)]
 pub struct PlannedRename {
     pub old_path: PathBuf,
     pub new_path: PathBuf,
     /// True if any warning conditions are met (e.g., season or episode equals "0").
     pub warn: bool,
+    /// What the executor will do with this file.
+    pub action: RenameAction,
+    /// Name of the rule whose pattern matched the file, if the plan was built from rules.
+    pub rule: Option<String>,
+}
+
+impl PlannedRename {
+    /// Creates a plain rename from `old_path` to `new_path`.
+    pub fn new(old_path: impl Into<PathBuf>, new_path: impl Into<PathBuf>) -> PlannedRename {
+        PlannedRename {
+            old_path: old_path.into(),
+            new_path: new_path.into(),
+            warn: false,
+            action: RenameAction::Rename,
+            rule: None,
+        }
+    }
+}
+
+/// The action the executor takes for a planned rename.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RenameAction {
+    /// Rename the file; the target must not exist.
+    #[default]
+    Rename,
+    /// Rename the file, replacing the existing file at the target.
+    Overwrite,
+    /// Leave the file untouched, for the given reason.
+    Skip(String),
+}
+
+impl RenameAction {
+    /// True if the file is left untouched.
+    pub fn is_skip(&self) -> bool {
+        matches!(self, RenameAction::Skip(_))
+    }
 }
 
 /// Transforms an original file name into a new one according to a template.
@@ -28,12 +68,13 @@ pub struct PlannedRename {
 /// 
 /// - `original`: The original file name.
 /// - `new_pattern`: The template for the new file name with placeholders in the form `{name}` or `{name:width}`,
-///    where `name` corresponds to a named capture group in the regex, and optional `width` formats numeric values with leading zeros.
+///   where `name` corresponds to a named capture group in the regex, and optional `width` formats numeric values with leading zeros.
 /// - `re`: The regex used to capture metadata from the original name.
 /// 
 /// # Returns
 /// 
 /// Returns `Ok(new_file_name)` if the regex matches; otherwise, returns `Err(RenamerError::InvalidPattern)`.
+/// Returns `Err(RenamerError::InvalidTemplate)` if `new_pattern` cannot be parsed (see [`Template`]).
 /// 
 /// # Examples
 /// 
@@ -59,43 +100,43 @@ pub fn transform_filename(
     new_pattern: &str,
     re: &Regex
 ) -> Result<String, RenamerError> {
-    let path = Path::new(original);
-    let original_ext = path
-        .extension()
-        .and_then(|s| s.to_str())
-        .unwrap_or("")
-        .to_lowercase();
+    let template = Template::parse(new_pattern)?;
+    transform_with_template(original, &template, re)
+}
 
+/// Transforms a file name with an already parsed [`Template`].
+///
+/// This is the hot-path counterpart of [`transform_filename`]: parse the template
+/// once, then call this for every file.
+///
+/// # Examples
+///
+/// ```
+/// # use regex::Regex;
+/// # use renamer::template::Template;
+/// # use renamer::renamer::transform_with_template;
+/// let re = Regex::new(r"S(?P<season>\d+)E(?P<episode>\d+)").unwrap();
+/// let template = Template::parse("Show - S{season:02}E{episode:02}").unwrap();
+/// assert_eq!(transform_with_template("S1E2.mkv", &template, &re).unwrap(), "Show - S01E02.mkv");
+/// ```
+pub fn transform_with_template(
+    original: &str,
+    template: &Template,
+    re: &Regex
+) -> Result<String, RenamerError> {
     // Capture groups from the original file name using the regex.
     let caps = re.captures(original).ok_or(RenamerError::InvalidPattern)?;
+    let new_file_name = template.render(&caps)?;
+    Ok(enforce_extension(new_file_name, original))
+}
 
-    // Replace placeholders of the form {name} or {name:width} in new_pattern.
-    let placeholder_re = Regex::new(r"\{(\w+)(?::(\d+))?\}").unwrap();
-    let result = placeholder_re.replace_all(new_pattern, |ph_caps: &regex::Captures| {
-        let key = &ph_caps[1];
-        if let Some(m) = caps.name(key) {
-            let value = m.as_str();
-            // If a width is provided, format the value accordingly.
-            if let Some(width_match) = ph_caps.get(2) {
-                let width: usize = width_match.as_str().parse().unwrap();
-                // For numeric values, ensure proper zero-padding
-                if let Ok(num_value) = value.parse::<usize>() {
-                    format!("{:0width$}", num_value, width = width)
-                } else {
-                    // Non-numeric values don't need zero-padding
-                    format!("{:width$}", value, width = width)
-                }
-            } else {
-                value.to_string()
-            }
-        } else {
-            // Replace with an empty string if the capture is missing.
-            "".to_string()
-        }
-    });
-    let mut new_file_name = result.to_string();
-
-    // Enforce the original file's extension.
+/// Enforces the original file's (lowercased) extension on a new file name.
+pub(crate) fn enforce_extension(mut new_file_name: String, original: &str) -> String {
+    let original_ext = Path::new(original)
+        .extension()
+        .and_then(|s| s.to_str())
+        .unwrap_or("")
+        .to_lowercase();
     let candidate = Path::new(&new_file_name);
     if let Some(candidate_ext) = candidate.extension().and_then(|s| s.to_str()) {
         if candidate_ext.to_lowercase() != original_ext {
@@ -105,7 +146,7 @@ pub fn transform_filename(
     } else if !original_ext.is_empty() {
         new_file_name = format!("{}.{}", new_file_name, original_ext);
     }
-    Ok(new_file_name)
+    new_file_name
 }
 
 /// Checks whether any named capture with specific values should trigger a warning.
@@ -246,7 +287,7 @@ mod tests {
     fn test_check_warning_no_warning() {
         let re = Regex::new(r"S(?P<season>\d+)E(?P<episode>\d+)").unwrap();
         let file_name = "S01E01_video.mkv";
-        assert_eq!(check_warning(file_name, &re), false);
+        assert!(!check_warning(file_name, &re));
     }
 
     #[test]
@@ -254,8 +295,8 @@ mod tests {
         let re = Regex::new(r"S(?P<season>\d+)E(?P<episode>\d+)").unwrap();
         let file_name1 = "S0E01_video.mkv";
         let file_name2 = "S01E0_video.mkv";
-        assert_eq!(check_warning(file_name1, &re), true);
-        assert_eq!(check_warning(file_name2, &re), true);
+        assert!(check_warning(file_name1, &re));
+        assert!(check_warning(file_name2, &re));
     }
 
     #[test]
@@ -0,0 +1,311 @@
+//! Report module for the renamer tool.
+//! This module writes the rename plan and the outcome of a run as rows with stable
+//! field names (`old`, `new`, `status`, `warnings`, `error`, `rule`), in JSON, CSV, TSV
+//! or as an aligned text table, so scripts do not have to scrape log lines.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::{self, Write};
+use std::path::Path;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use crate::executor::ExecutionReport;
+use crate::renamer::{PlannedRename, RenameAction};
+
+/// Field names, in output order.
+pub const FIELDS: [&str; 6] = ["old", "new", "status", "warnings", "error", "rule"];
+
+/// Machine-readable output formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// A JSON array of objects.
+    Json,
+    /// Comma-separated values with a header line.
+    Csv,
+    /// Tab-separated values with a header line.
+    Tsv,
+    /// An aligned text table.
+    Table,
+}
+
+/// What happened, or will happen, to a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    /// The file will be renamed (dry run).
+    Planned,
+    /// The new name equals the old one.
+    Unchanged,
+    /// The file is left untouched by the conflict policy.
+    Skipped,
+    /// The file was renamed.
+    Renamed,
+    /// Renaming the file failed.
+    Failed,
+    /// The file was renamed, then restored by an atomic rollback.
+    RolledBack,
+    /// The file was not renamed because an atomic run stopped before reaching it.
+    NotRun,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Status::Planned => "planned",
+            Status::Unchanged => "unchanged",
+            Status::Skipped => "skipped",
+            Status::Renamed => "renamed",
+            Status::Failed => "failed",
+            Status::RolledBack => "rolled_back",
+            Status::NotRun => "not_run",
+        };
+        f.write_str(s)
+    }
+}
+
+/// One row of a report.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ReportRow {
+    pub old: String,
+    pub new: String,
+    pub status: Status,
+    pub warnings: Vec<String>,
+    pub error: Option<String>,
+    /// Name of the rule that matched the file.
+    pub rule: Option<String>,
+}
+
+impl ReportRow {
+    fn from_plan(plan: &PlannedRename, status: Status) -> ReportRow {
+        let mut warnings = Vec::new();
+        if plan.warn {
+            warnings.push("season or episode is 0".to_string());
+        }
+        match &plan.action {
+            RenameAction::Rename => {}
+            RenameAction::Overwrite => warnings.push("overwrites an existing file".to_string()),
+            RenameAction::Skip(reason) => warnings.push(reason.clone()),
+        }
+        ReportRow {
+            old: plan.old_path.display().to_string(),
+            new: plan.new_path.display().to_string(),
+            status,
+            warnings,
+            error: None,
+            rule: plan.rule.clone(),
+        }
+    }
+
+    fn fields(&self) -> [String; 6] {
+        [
+            self.old.clone(),
+            self.new.clone(),
+            self.status.to_string(),
+            self.warnings.join("; "),
+            self.error.clone().unwrap_or_default(),
+            self.rule.clone().unwrap_or_default(),
+        ]
+    }
+}
+
+/// Returns one row per planned rename, describing what a run would do.
+pub fn plan_rows(plan: &[PlannedRename]) -> Vec<ReportRow> {
+    plan.iter()
+        .map(|p| ReportRow::from_plan(p, planned_status(p).unwrap_or(Status::Planned)))
+        .collect()
+}
+
+/// Returns one row per planned rename, describing the outcome of executing `plan`.
+///
+/// A rename counts as performed when a step moved a file onto its target, and as
+/// failed when a step from its source or onto its target failed; this also covers the
+/// temporary moves used to break cycles.
+pub fn result_rows(plan: &[PlannedRename], report: &ExecutionReport) -> Vec<ReportRow> {
+    // Index the steps by path so large runs are reported in linear time. Failures keep
+    // their position, so the first failure touching a rename is the one reported.
+    let mut failed_from: HashMap<&Path, usize> = HashMap::new();
+    let mut failed_to: HashMap<&Path, usize> = HashMap::new();
+    for (index, (step, _)) in report.failed.iter().enumerate() {
+        failed_from.entry(&step.from).or_insert(index);
+        failed_to.entry(&step.to).or_insert(index);
+    }
+    let performed: HashSet<&Path> = report.performed.iter().map(|step| step.to.as_path()).collect();
+    let restored: HashSet<&Path> = report.restored.iter().map(|step| step.from.as_path()).collect();
+    plan.iter()
+        .map(|p| {
+            if let Some(status) = planned_status(p) {
+                return ReportRow::from_plan(p, status);
+            }
+            let failure = [failed_from.get(p.old_path.as_path()), failed_to.get(p.new_path.as_path())]
+                .into_iter()
+                .flatten()
+                .min()
+                .map(|&index| &report.failed[index]);
+            let performed = performed.contains(p.new_path.as_path());
+            let restored = restored.contains(p.new_path.as_path());
+            let (status, error) = match failure {
+                Some((_, e)) => (Status::Failed, Some(e.clone())),
+                None if performed && restored => (Status::RolledBack, None),
+                None if performed => (Status::Renamed, None),
+                None => (Status::NotRun, None),
+            };
+            ReportRow { error, ..ReportRow::from_plan(p, status) }
+        })
+        .collect()
+}
+
+/// The status of renames that the executor does not perform.
+fn planned_status(plan: &PlannedRename) -> Option<Status> {
+    if plan.action.is_skip() {
+        Some(Status::Skipped)
+    } else if plan.old_path == plan.new_path {
+        Some(Status::Unchanged)
+    } else {
+        None
+    }
+}
+
+/// Writes `rows` to `out` in the given format.
+///
+/// # Errors
+///
+/// Returns an error if writing to `out` fails.
+///
+/// # Examples
+///
+/// ```
+/// # use renamer::PlannedRename;
+/// # use renamer::report::{plan_rows, write_rows, OutputFormat};
+/// let rows = plan_rows(&[PlannedRename::new("a.mkv", "b.mkv")]);
+/// let mut out = Vec::new();
+/// write_rows(&mut out, &rows, OutputFormat::Csv).unwrap();
+/// assert_eq!(String::from_utf8(out).unwrap(), "old,new,status,warnings,error,rule\na.mkv,b.mkv,planned,,,\n");
+/// ```
+pub fn write_rows(out: &mut impl Write, rows: &[ReportRow], format: OutputFormat) -> io::Result<()> {
+    match format {
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut *out, rows)?;
+            writeln!(out)
+        }
+        OutputFormat::Csv => write_delimited(out, rows, ',', csv_field),
+        OutputFormat::Tsv => write_delimited(out, rows, '\t', tsv_field),
+        OutputFormat::Table => write_table(out, rows),
+    }
+}
+
+fn write_delimited(
+    out: &mut impl Write,
+    rows: &[ReportRow],
+    separator: char,
+    escape: fn(&str) -> String,
+) -> io::Result<()> {
+    writeln!(out, "{}", FIELDS.join(&separator.to_string()))?;
+    for row in rows {
+        let fields: Vec<String> = row.fields().iter().map(|f| escape(f)).collect();
+        writeln!(out, "{}", fields.join(&separator.to_string()))?;
+    }
+    Ok(())
+}
+
+/// Quotes a CSV field if it contains a separator, quote or line break (RFC 4180).
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// TSV has no quoting, so tabs and line breaks inside a field are replaced by spaces.
+fn tsv_field(field: &str) -> String {
+    field.replace(['\t', '\n', '\r'], " ")
+}
+
+fn write_table(out: &mut impl Write, rows: &[ReportRow]) -> io::Result<()> {
+    let cells: Vec<[String; 6]> = rows.iter().map(ReportRow::fields).collect();
+    let mut widths = FIELDS.map(|f| f.chars().count());
+    for row in &cells {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let line = |out: &mut dyn Write, cells: &[String]| -> io::Result<()> {
+        let padded: Vec<String> = cells.iter().zip(&widths).map(|(c, &w)| format!("{:<w$}", c, w = w)).collect();
+        writeln!(out, "{}", padded.join("  ").trim_end())
+    };
+    line(out, &FIELDS.map(String::from))?;
+    line(out, &widths.map(|w| "-".repeat(w)))?;
+    for row in &cells {
+        line(out, row)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::Step;
+
+    fn render(rows: &[ReportRow], format: OutputFormat) -> String {
+        let mut out = Vec::new();
+        write_rows(&mut out, rows, format).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_plan_rows_formats() {
+        let mut zero = PlannedRename::new("/d/S0E1.mkv", "/d/Show, S00E01.mkv");
+        zero.warn = true;
+        zero.rule = Some("#1".to_string());
+        let mut skipped = PlannedRename::new("/d/b.mkv", "/d/taken.mkv");
+        skipped.action = RenameAction::Skip("target exists".to_string());
+        let rows = plan_rows(&[zero, skipped, PlannedRename::new("/d/same.mkv", "/d/same.mkv")]);
+        assert_eq!(rows[0].status, Status::Planned);
+        assert_eq!(rows[0].warnings, vec!["season or episode is 0"]);
+        assert_eq!(rows[1].status, Status::Skipped);
+        assert_eq!(rows[2].status, Status::Unchanged);
+
+        let csv = render(&rows, OutputFormat::Csv);
+        assert_eq!(csv.lines().nth(1).unwrap(), r#"/d/S0E1.mkv,"/d/Show, S00E01.mkv",planned,season or episode is 0,,#1"#);
+
+        let tsv = render(&rows, OutputFormat::Tsv);
+        assert_eq!(tsv.lines().next().unwrap(), "old\tnew\tstatus\twarnings\terror\trule");
+        assert_eq!(tsv.lines().nth(2).unwrap(), "/d/b.mkv\t/d/taken.mkv\tskipped\ttarget exists\t\t");
+
+        let json: serde_json::Value = serde_json::from_str(&render(&rows, OutputFormat::Json)).unwrap();
+        assert_eq!(json[1]["status"], "skipped");
+        assert_eq!(json[1]["warnings"][0], "target exists");
+        assert!(json[1]["error"].is_null());
+        assert_eq!(json[0]["rule"], "#1");
+
+        let table = render(&rows, OutputFormat::Table);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert!(lines[0].starts_with("old          new"));
+        assert_eq!(lines[3].find("skipped"), lines[0].find("status"));
+    }
+
+    #[test]
+    fn test_result_rows() {
+        let plan = vec![
+            PlannedRename::new("/d/a", "/d/x"),
+            PlannedRename::new("/d/b", "/d/y"),
+            PlannedRename::new("/d/c", "/d/z"),
+        ];
+        let report = ExecutionReport {
+            performed: vec![Step::new("/d/a", "/d/x")],
+            failed: vec![(Step::new("/d/b", "/d/y"), "permission denied".to_string())],
+            restored: vec![Step::new("/d/x", "/d/a")],
+            ..Default::default()
+        };
+        let rows = result_rows(&plan, &report);
+        assert_eq!(rows[0].status, Status::RolledBack);
+        assert_eq!(rows[1].status, Status::Failed);
+        assert_eq!(rows[1].error.as_deref(), Some("permission denied"));
+        assert_eq!(rows[2].status, Status::NotRun);
+
+        let report = ExecutionReport { performed: vec![Step::new("/d/c", "/d/z")], ..Default::default() };
+        assert_eq!(result_rows(&plan[2..], &report)[0].status, Status::Renamed);
+    }
+}
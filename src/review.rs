@@ -0,0 +1,283 @@
+//! Review module for the renamer tool.
+//! In interactive mode (`--interactive`) the plan is walked file by file and the user
+//! accepts, skips or edits each rename, or accepts all remaining ones. Skipped files
+//! stay in the plan as [`RenameAction::Skip`] so reports show them.
+//!
+//! The plan is reviewed after its conflicts were resolved, so the names and actions
+//! shown are the ones that would be applied. Edited names are resolved again
+//! afterwards, and renames that this changes are shown again (see [`review_resolved`]).
+
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use crate::file_ops::file_name;
+use crate::plan::{resolve_conflicts, Conflict, ConflictPolicy};
+use crate::renamer::{PlannedRename, RenameAction};
+
+/// Reason recorded for files skipped during review.
+pub const SKIPPED_IN_REVIEW: &str = "skipped during review";
+
+/// Walks `plan` with the user, reading answers from `input` and writing prompts to `output`.
+///
+/// Returns the reviewed plan, or `None` if the user quit (or `input` ended), in which
+/// case nothing should be renamed. Renames whose source and target are equal are not
+/// asked about, and renames already skipped (for example by the conflict policy) are
+/// only listed. Editing a name resets its action to [`RenameAction::Rename`], so the
+/// edited plan should have its conflicts resolved again.
+///
+/// # Errors
+///
+/// Returns an error if reading `input` or writing `output` fails.
+///
+/// # Examples
+///
+/// ```
+/// # use renamer::PlannedRename;
+/// # use renamer::review::review;
+/// let plan = vec![PlannedRename::new("/d/a", "/d/b"), PlannedRename::new("/d/c", "/d/d")];
+/// let mut output = Vec::new();
+/// let reviewed = review(&plan, &mut "n\na\n".as_bytes(), &mut output).unwrap().unwrap();
+/// assert!(reviewed[0].action.is_skip());
+/// assert!(!reviewed[1].action.is_skip());
+/// ```
+pub fn review(plan: &[PlannedRename], input: &mut impl BufRead, output: &mut impl Write) -> io::Result<Option<Vec<PlannedRename>>> {
+    review_some(plan, &(0..plan.len()).collect::<Vec<_>>(), input, output)
+}
+
+/// Reviews `plan` like [`review`], then resolves its conflicts again with `policy`, as
+/// edited names may clash with other targets.
+///
+/// Renames the second resolution changes (renamed with a suffix or skipped) are shown
+/// again: changed names are asked about once more, and newly skipped renames must be
+/// confirmed. This repeats until resolving changes nothing. Returns the plan with the
+/// conflicts that remain, or `None` if the user quit.
+///
+/// # Errors
+///
+/// Returns an error if reading `input` or writing `output` fails.
+pub fn review_resolved(
+    plan: &[PlannedRename],
+    policy: ConflictPolicy,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> io::Result<Option<(Vec<PlannedRename>, Vec<Conflict>)>> {
+    let mut plan = plan.to_vec();
+    let mut only: Vec<usize> = (0..plan.len()).collect();
+    loop {
+        let Some(reviewed) = review_some(&plan, &only, input, output)? else {
+            return Ok(None);
+        };
+        let mut resolved = reviewed.clone();
+        let conflicts = resolve_conflicts(&mut resolved, policy);
+        only = (0..resolved.len()).filter(|&i| resolved[i] != reviewed[i]).collect();
+        if only.is_empty() {
+            return Ok(Some((resolved, conflicts)));
+        }
+        writeln!(output, "Resolving conflicts again changed {} rename(s):", only.len())?;
+        if only.iter().any(|&i| resolved[i].action.is_skip()) {
+            for &i in only.iter().filter(|&&i| resolved[i].action.is_skip()) {
+                if let RenameAction::Skip(reason) = &resolved[i].action {
+                    writeln!(output, "Skipping {}: {}", file_name(&resolved[i].old_path), reason)?;
+                }
+            }
+            if !confirm("Continue without these renames? [y]es, [q]uit: ", input, output)? {
+                return Ok(None);
+            }
+            only.retain(|&i| !resolved[i].action.is_skip());
+        }
+        plan = resolved;
+    }
+}
+
+/// Reviews the renames of `plan` at the indices `only`.
+fn review_some(
+    plan: &[PlannedRename],
+    only: &[usize],
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> io::Result<Option<Vec<PlannedRename>>> {
+    let mut reviewed = plan.to_vec();
+    let changing = |i: &usize| plan[*i].old_path != plan[*i].new_path;
+    for p in only.iter().filter(|i| changing(i)).map(|&i| &plan[i]) {
+        if let RenameAction::Skip(reason) = &p.action {
+            writeln!(output, "Skipping {}: {}", file_name(&p.old_path), reason)?;
+        }
+    }
+    let pending: Vec<usize> = only.iter().copied().filter(|i| changing(i) && !plan[*i].action.is_skip()).collect();
+    let total = pending.len();
+    for (pos, &idx) in pending.iter().enumerate() {
+        let p = &mut reviewed[idx];
+        writeln!(output, "[{}/{}] {}", pos + 1, total, file_name(&p.old_path))?;
+        writeln!(output, "    -> {}", file_name(&p.new_path))?;
+        if p.action == RenameAction::Overwrite {
+            writeln!(output, "    warning: replaces the existing {}", file_name(&p.new_path))?;
+        }
+        if p.warn {
+            writeln!(output, "    warning: season or episode is 0")?;
+        }
+        loop {
+            write!(output, "Rename? [y]es, [n]o, [e]dit, [a]ll remaining, [q]uit: ")?;
+            output.flush()?;
+            let Some(answer) = read_answer(input)? else {
+                return Ok(None);
+            };
+            match answer.to_lowercase().as_str() {
+                "y" | "yes" | "" => break,
+                "n" | "no" | "s" | "skip" => {
+                    p.action = RenameAction::Skip(SKIPPED_IN_REVIEW.to_string());
+                    break;
+                }
+                "e" | "edit" => {
+                    let Some(name) = ask_name(&p.new_path, input, output)? else {
+                        return Ok(None);
+                    };
+                    let new_path = p.old_path.with_file_name(name);
+                    if new_path != p.new_path {
+                        p.new_path = new_path;
+                        p.action = RenameAction::Rename;
+                    }
+                    break;
+                }
+                "a" | "all" => return Ok(Some(reviewed)),
+                "q" | "quit" => return Ok(None),
+                _ => writeln!(output, "Please answer y, n, e, a or q.")?,
+            }
+        }
+    }
+    Ok(Some(reviewed))
+}
+
+/// Asks for a new file name until a valid one is given; an empty answer keeps `current`.
+fn ask_name(current: &Path, input: &mut impl BufRead, output: &mut impl Write) -> io::Result<Option<String>> {
+    loop {
+        write!(output, "New name [{}]: ", file_name(current))?;
+        output.flush()?;
+        let Some(name) = read_answer(input)? else {
+            return Ok(None);
+        };
+        if name.is_empty() {
+            return Ok(Some(file_name(current)));
+        }
+        if name.contains('/') || name.contains(std::path::MAIN_SEPARATOR) {
+            writeln!(output, "The new name must not contain a path separator.")?;
+            continue;
+        }
+        return Ok(Some(name));
+    }
+}
+
+/// Asks `question` until it is answered with yes or quit; the end of input means quit.
+fn confirm(question: &str, input: &mut impl BufRead, output: &mut impl Write) -> io::Result<bool> {
+    loop {
+        write!(output, "{}", question)?;
+        output.flush()?;
+        match read_answer(input)?.map(|a| a.to_lowercase()).as_deref() {
+            Some("y" | "yes") => return Ok(true),
+            Some("q" | "quit") | None => return Ok(false),
+            Some(_) => writeln!(output, "Please answer y or q.")?,
+        }
+    }
+}
+
+/// Reads one trimmed line, or `None` at the end of input.
+fn read_answer(input: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(line.trim().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn sample_plan() -> Vec<PlannedRename> {
+        vec![
+            PlannedRename::new("/d/a.mkv", "/d/A.mkv"),
+            PlannedRename::new("/d/same.mkv", "/d/same.mkv"),
+            PlannedRename::new("/d/b.mkv", "/d/B.mkv"),
+            PlannedRename::new("/d/c.mkv", "/d/C.mkv"),
+        ]
+    }
+
+    fn run(answers: &str) -> (Option<Vec<PlannedRename>>, String) {
+        let mut output = Vec::new();
+        let result = review(&sample_plan(), &mut answers.as_bytes(), &mut output).unwrap();
+        (result, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn test_accept_skip_edit() {
+        let (result, output) = run("maybe\ny\ne\nsub/x.mkv\nFixed.mkv\nn\n");
+        let result = result.unwrap();
+        assert_eq!(result[0], sample_plan()[0]);
+        assert_eq!(result[2].new_path, PathBuf::from("/d/Fixed.mkv"));
+        assert_eq!(result[3].action, RenameAction::Skip(SKIPPED_IN_REVIEW.to_string()));
+        assert!(output.contains("[1/3] a.mkv"));
+        assert!(output.contains("Please answer"));
+        assert!(output.contains("must not contain a path separator"));
+        assert!(!output.contains("same.mkv"));
+    }
+
+    #[test]
+    fn test_accept_all_and_quit() {
+        let (result, _) = run("n\na\n");
+        let result = result.unwrap();
+        assert!(result[0].action.is_skip());
+        assert!(!result[2].action.is_skip());
+        assert!(!result[3].action.is_skip());
+
+        assert_eq!(run("y\nq\n").0, None);
+        assert_eq!(run("y\n").0, None);
+        assert_eq!(run("e\n\ny\ny\n").0.unwrap(), sample_plan());
+    }
+
+    #[test]
+    fn test_edited_name_collides() {
+        let plan = vec![PlannedRename::new("/d/a.mkv", "/d/A.mkv"), PlannedRename::new("/d/b.mkv", "/d/B.mkv")];
+        let run = |policy, answers: &str| {
+            let mut output = Vec::new();
+            let result = review_resolved(&plan, policy, &mut answers.as_bytes(), &mut output).unwrap();
+            (result, String::from_utf8(output).unwrap())
+        };
+
+        // The suffixed name is shown again before it is accepted.
+        let (result, output) = run(ConflictPolicy::Suffix, "y\ne\nA.mkv\ny\n");
+        let (result, conflicts) = result.unwrap();
+        assert!(conflicts.is_empty());
+        assert_eq!(result[1].new_path, PathBuf::from("/d/A (2).mkv"));
+        assert!(output.contains("changed 1 rename(s)"), "{}", output);
+        assert!(output.contains("[1/1] b.mkv\n    -> A (2).mkv"), "{}", output);
+        assert_eq!(run(ConflictPolicy::Suffix, "y\ne\nA.mkv\nq\n").0, None);
+
+        // A rename dropped by the skip policy must be confirmed.
+        let (result, output) = run(ConflictPolicy::Skip, "y\ne\nA.mkv\ny\n");
+        let (result, _) = result.unwrap();
+        assert!(result[1].action.is_skip());
+        assert!(output.contains("Skipping b.mkv: target \"/d/A.mkv\" is claimed by another file"), "{}", output);
+        assert!(output.contains("Continue without these renames?"), "{}", output);
+        assert_eq!(run(ConflictPolicy::Skip, "y\ne\nA.mkv\nq\n").0, None);
+
+        // With the abort policy nothing changes and the conflict is returned.
+        let (result, _) = run(ConflictPolicy::Abort, "y\ne\nA.mkv\n");
+        assert_eq!(result.unwrap().1.len(), 1);
+    }
+
+    #[test]
+    fn test_resolved_actions() {
+        let mut plan = sample_plan();
+        plan[0].action = RenameAction::Overwrite;
+        plan[2].action = RenameAction::Skip("target exists".to_string());
+        let mut output = Vec::new();
+        let result = review(&plan, &mut "e\nNew.mkv\ny\n".as_bytes(), &mut output).unwrap().unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Skipping b.mkv: target exists"), "{}", output);
+        assert!(output.contains("[1/2] a.mkv\n    -> A.mkv\n    warning: replaces the existing A.mkv"), "{}", output);
+        assert!(output.contains("[2/2] c.mkv"), "{}", output);
+        // An edited name no longer overwrites anything until conflicts are resolved again.
+        assert_eq!(result[0].new_path, PathBuf::from("/d/New.mkv"));
+        assert_eq!(result[0].action, RenameAction::Rename);
+        assert_eq!(result[2], plan[2]);
+    }
+}
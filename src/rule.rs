@@ -0,0 +1,131 @@
+//! Rule module for the renamer tool.
+//! A rule pairs a current-name pattern with a new-name template. Several rules can be
+//! given; each file is renamed by the first rule whose pattern matches it, so one run
+//! can handle a folder mixing `S01E02`, `1x02` and `Ep 02` style names.
+
+use std::fmt;
+use std::str::FromStr;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use crate::error::RenamerError;
+use crate::template::Template;
+
+/// Separator between the pattern and the template of a rule given on the command line.
+pub const RULE_SEPARATOR: &str = "=>";
+
+/// A rule as written in a config file or on the command line, not yet compiled.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleSpec {
+    /// Name shown in the plan; defaults to the rule's position (`#1`, `#2`, ...).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Regex with named groups matched against file names.
+    pub pattern: String,
+    /// New-name template (see [`Template`]).
+    pub template: String,
+}
+
+impl FromStr for RuleSpec {
+    type Err = String;
+
+    /// Parses `PATTERN=>TEMPLATE`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (pattern, template) = s
+            .split_once(RULE_SEPARATOR)
+            .ok_or_else(|| format!("expected PATTERN{}TEMPLATE", RULE_SEPARATOR))?;
+        Ok(RuleSpec { name: None, pattern: pattern.to_string(), template: template.to_string() })
+    }
+}
+
+/// A compiled rule.
+#[derive(Debug, Clone)]
+pub struct Rule {
+    pub name: String,
+    pub pattern: Regex,
+    pub template: Template,
+}
+
+impl Rule {
+    /// Compiles `spec`, the rule at (0-based) position `index`, checking that every
+    /// placeholder of its template is a named group of its pattern (for the default
+    /// template, every placeholder outside its optional segment).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RenamerError::InvalidRule`] naming the rule if the pattern is not a valid
+    /// regex, the template cannot be parsed or it uses an unknown group.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use renamer::rule::{Rule, RuleSpec};
+    /// let spec: RuleSpec = r"(?P<season>\d+)x(?P<episode>\d+)=>S{season:02}E{episode:02}".parse().unwrap();
+    /// let rule = Rule::compile(&spec, 1).unwrap();
+    /// assert_eq!(rule.name, "#2");
+    /// assert!(Rule::compile(&"(?P<a>.+)=>{b}".parse().unwrap(), 0).is_err());
+    /// ```
+    pub fn compile(spec: &RuleSpec, index: usize) -> Result<Rule, RenamerError> {
+        let name = spec.name.clone().unwrap_or_else(|| format!("#{}", index + 1));
+        let invalid = |message: String| RenamerError::InvalidRule { name: name.clone(), message };
+        let pattern = Regex::new(&spec.pattern).map_err(|e| invalid(format!("invalid regex pattern: {}", e)))?;
+        let template = Template::parse(&spec.template).map_err(|e| invalid(e.to_string()))?;
+        template.check_groups(&pattern).map_err(|e| invalid(e.to_string()))?;
+        Ok(Rule { name: name.clone(), pattern, template })
+    }
+
+    /// Returns the first of `rules` whose pattern matches `file_name`.
+    pub fn first_match<'a>(rules: &'a [Rule], file_name: &str) -> Option<&'a Rule> {
+        rules.iter().find(|rule| rule.pattern.is_match(file_name))
+    }
+}
+
+impl fmt::Display for RuleSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}{}", self.pattern, RULE_SEPARATOR, self.template)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules() -> Vec<Rule> {
+        [
+            r"S(?P<season>\d+)E(?P<episode>\d+)=>S{season:02}E{episode:02}",
+            r"(?P<season>\d+)x(?P<episode>\d+)=>S{season:02}E{episode:02}",
+            r"Ep (?P<episode>\d+)=>S01E{episode:02}",
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, s)| Rule::compile(&s.parse().unwrap(), i).unwrap())
+        .collect()
+    }
+
+    #[test]
+    fn test_parse_spec() {
+        let spec: RuleSpec = "a(?P<x>b)=>{x}=>".parse().unwrap();
+        assert_eq!(spec.pattern, "a(?P<x>b)");
+        assert_eq!(spec.template, "{x}=>");
+        assert_eq!(spec.to_string(), "a(?P<x>b)=>{x}=>");
+        assert!("no separator".parse::<RuleSpec>().is_err());
+    }
+
+    #[test]
+    fn test_first_match() {
+        let rules = rules();
+        assert_eq!(Rule::first_match(&rules, "Show S1E2.mkv").unwrap().name, "#1");
+        assert_eq!(Rule::first_match(&rules, "Show 1x02.mkv").unwrap().name, "#2");
+        assert_eq!(Rule::first_match(&rules, "Show Ep 3.mkv").unwrap().name, "#3");
+        assert!(Rule::first_match(&rules, "notes.txt").is_none());
+    }
+
+    #[test]
+    fn test_compile_errors_name_the_rule() {
+        let spec = RuleSpec { name: Some("anime".into()), pattern: "(".into(), template: "x".into() };
+        let err = Rule::compile(&spec, 0).unwrap_err().to_string();
+        assert!(err.contains("anime"), "{}", err);
+        let err = Rule::compile(&"(?P<a>.)=>{b}".parse().unwrap(), 2).unwrap_err().to_string();
+        assert!(err.contains("#3") && err.contains("b"), "{}", err);
+    }
+}
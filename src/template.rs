@@ -0,0 +1,609 @@
+//! Template module for the renamer tool.
+//! A [`Template`] is the parsed form of a new-name pattern such as
+//! `"{title} - S{season:02}E{episode:02}"`. Parsing once up front lets syntax errors
+//! and placeholders that do not match the regex be reported before any file is
+//! processed, and keeps the per-file work down to a single pass over the segments.
+//!
+//! # Syntax
+//!
+//! - `{name}` is replaced by the value of the named capture group `name`.
+//! - `{name:width}` zero-pads numeric values (and space-pads other values) to `width`.
+//! - `{name|filter|filter:arg:arg}` pipes the value through filters, left to right,
+//!   before padding. Available filters are `lower`, `upper`, `title_case`, `slug`,
+//!   `trim` and `replace:FROM:TO`.
+//! - `{name+N}`, `{name-N}`, `{name*N}` and `{name/N}` do integer arithmetic on a
+//!   numeric value, e.g. `{episode-25:02}` turns absolute episode 26 into `01`.
+//!   Operators are applied left to right; a non-numeric value is an error.
+//! - `{n}` is the sequence counter (see [`crate::plan::Sequence`]) unless the regex
+//!   defines a group named `n`; `{n:03}` pads it like any other number.
+//! - `{name?default}` uses `default` when the group did not match or matched nothing.
+//! - `[...]` is an optional segment: it is emitted only when every placeholder directly
+//!   inside it has a value, e.g. `{title}[ - Part {part}]`. Brackets around plain text,
+//!   such as `[Group]`, are kept as-is.
+//! - `{{`, `}}`, `[[` and `]]` produce literal braces and brackets.
+//!
+//! The parts of a placeholder combine in the order `{name+N?default:width|filter}`;
+//! the default replaces a missing value before arithmetic is applied.
+//!
+//! # Examples
+//!
+//! ```
+//! # use regex::Regex;
+//! # use renamer::template::Template;
+//! let re = Regex::new(r"S(?P<season>\d+)E(?P<episode>\d+)").unwrap();
+//! let template = Template::parse("Show - S{season:02}E{episode:02}").unwrap();
+//! template.check_groups(&re).unwrap();
+//! let caps = re.captures("S1E5.mkv").unwrap();
+//! assert_eq!(template.render(&caps).unwrap(), "Show - S01E05");
+//!
+//! // Clean up a dotted release name.
+//! let re = Regex::new(r"^(?P<title>[\w.]+)\.S\d+").unwrap();
+//! let template = Template::parse("{title|replace:.: |title_case}").unwrap();
+//! let caps = re.captures("my.show.name.S01E01.mkv").unwrap();
+//! assert_eq!(template.render(&caps).unwrap(), "My Show Name");
+//! ```
+
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+use regex::{Captures, Regex};
+use crate::error::RenamerError;
+
+/// The placeholder name of the sequence counter.
+pub const COUNTER: &str = "n";
+
+/// A parsed new-name template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    source: String,
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Placeholder(Placeholder),
+    /// A `[...]` segment emitted only when its placeholders have values.
+    Optional(Vec<Segment>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Placeholder {
+    name: String,
+    /// Arithmetic applied, in order, to the numeric value.
+    ops: Vec<(Op, i64)>,
+    default: Option<String>,
+    width: Option<usize>,
+    filters: Vec<Filter>,
+}
+
+/// An arithmetic operator in a placeholder such as `{episode-25}`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+impl Op {
+    fn from_char(c: char) -> Option<Op> {
+        match c {
+            '+' => Some(Op::Add),
+            '-' => Some(Op::Sub),
+            '*' => Some(Op::Mul),
+            '/' => Some(Op::Div),
+            _ => None,
+        }
+    }
+
+    fn apply(self, lhs: i64, rhs: i64) -> Option<i64> {
+        match self {
+            Op::Add => lhs.checked_add(rhs),
+            Op::Sub => lhs.checked_sub(rhs),
+            Op::Mul => lhs.checked_mul(rhs),
+            Op::Div => lhs.checked_div(rhs),
+        }
+    }
+}
+
+/// A transformation applied to a placeholder's value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Filter {
+    Lower,
+    Upper,
+    TitleCase,
+    Slug,
+    Trim,
+    Replace(String, String),
+}
+
+impl Filter {
+    /// Parses a filter such as `lower` or `replace:_: `.
+    fn parse(spec: &str) -> Result<Filter, RenamerError> {
+        let mut parts = spec.split(':');
+        let name = parts.next().unwrap_or_default();
+        let args: Vec<&str> = parts.collect();
+        let filter = match (name, args.as_slice()) {
+            ("lower", []) => Filter::Lower,
+            ("upper", []) => Filter::Upper,
+            ("title_case", []) => Filter::TitleCase,
+            ("slug", []) => Filter::Slug,
+            ("trim", []) => Filter::Trim,
+            ("replace", [from, to]) if !from.is_empty() => Filter::Replace(from.to_string(), to.to_string()),
+            ("replace", _) => {
+                return Err(RenamerError::InvalidTemplate(format!(
+                    "filter 'replace' expects 'replace:FROM:TO', got '{}'",
+                    spec
+                )));
+            }
+            ("lower" | "upper" | "title_case" | "slug" | "trim", _) => {
+                return Err(RenamerError::InvalidTemplate(format!("filter '{}' takes no arguments", name)));
+            }
+            _ => return Err(RenamerError::InvalidTemplate(format!("unknown filter '{}'", name))),
+        };
+        Ok(filter)
+    }
+
+    fn apply(&self, value: &str) -> String {
+        match self {
+            Filter::Lower => value.to_lowercase(),
+            Filter::Upper => value.to_uppercase(),
+            Filter::TitleCase => title_case(value),
+            Filter::Slug => slug(value),
+            Filter::Trim => value.trim().to_string(),
+            Filter::Replace(from, to) => value.replace(from.as_str(), to),
+        }
+    }
+}
+
+impl Template {
+    /// Parses a template.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RenamerError::InvalidTemplate`] for unbalanced braces or brackets,
+    /// empty or malformed placeholders, and invalid widths.
+    pub fn parse(pattern: &str) -> Result<Template, RenamerError> {
+        let mut parser = Parser { pattern, rest: pattern };
+        let segments = parser.segments(None)?;
+        Ok(Template { source: pattern.to_string(), segments })
+    }
+
+    /// The names referenced by the template's placeholders, in order of appearance.
+    pub fn placeholders(&self) -> impl Iterator<Item = &str> {
+        let mut found = Vec::new();
+        collect_placeholders(&self.segments, &mut found);
+        found.into_iter().map(|p| p.name.as_str())
+    }
+
+    /// Checks that every placeholder the template always emits refers to a named group
+    /// of `re`.
+    ///
+    /// Placeholders with a default value, and those inside optional segments (which are
+    /// then never emitted), may name groups the regex does not define. `{n}` refers to
+    /// the sequence counter unless the regex defines a group `n`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RenamerError::UnknownPlaceholder`] for the first required placeholder
+    /// without a matching group.
+    pub fn check_groups(&self, re: &Regex) -> Result<(), RenamerError> {
+        let groups: Vec<&str> = re.capture_names().flatten().collect();
+        let known = |name: &str| name == COUNTER || groups.contains(&name);
+        let missing = self.segments.iter().find_map(|s| match s {
+            Segment::Placeholder(p) if p.default.is_none() && !known(&p.name) => Some(p),
+            _ => None,
+        });
+        match missing {
+            Some(p) => Err(RenamerError::UnknownPlaceholder(p.name.clone())),
+            None => Ok(()),
+        }
+    }
+
+    /// Renders the template with the values captured from a file name.
+    ///
+    /// Placeholders whose group did not match (or matched an empty string) use their
+    /// default, or render as an empty string (after filters and padding) if they have
+    /// none. Optional segments are dropped unless every placeholder directly inside
+    /// them has a value.
+    pub fn render(&self, caps: &Captures) -> Result<String, RenamerError> {
+        self.render_with(caps, None)
+    }
+
+    /// Renders the template like [`Template::render`], with `counter` as the value of
+    /// the `{n}` sequence counter placeholder.
+    pub fn render_with(&self, caps: &Captures, counter: Option<i64>) -> Result<String, RenamerError> {
+        let mut out = String::new();
+        render_segments(&self.segments, caps, counter, &mut out)?;
+        Ok(out)
+    }
+
+    /// True if the template uses the `{n}` sequence counter.
+    pub fn uses_counter(&self) -> bool {
+        self.placeholders().any(|name| name == COUNTER)
+    }
+}
+
+/// A recursive-descent parser over the template source.
+struct Parser<'a> {
+    pattern: &'a str,
+    rest: &'a str,
+}
+
+impl Parser<'_> {
+    fn offset(&self) -> usize {
+        self.pattern.len() - self.rest.len()
+    }
+
+    /// Parses segments until the end of input, or until the `]` closing an optional
+    /// segment opened at `open`.
+    fn segments(&mut self, open: Option<usize>) -> Result<Vec<Segment>, RenamerError> {
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        while let Some(pos) = self.rest.find(['{', '}', '[', ']']) {
+            literal.push_str(&self.rest[..pos]);
+            let offset = self.offset() + pos;
+            let c = self.rest.as_bytes()[pos] as char;
+            self.rest = &self.rest[pos + 1..];
+            if let Some(after) = self.rest.strip_prefix(c) {
+                // Doubled brace or bracket: literal.
+                literal.push(c);
+                self.rest = after;
+                continue;
+            }
+            match c {
+                '{' => {
+                    let end = self.rest.find('}').ok_or_else(|| {
+                        RenamerError::InvalidTemplate(format!("unclosed '{{' at position {}", offset))
+                    })?;
+                    let placeholder = parse_placeholder(&self.rest[..end])?;
+                    self.rest = &self.rest[end + 1..];
+                    flush(&mut literal, &mut segments);
+                    segments.push(Segment::Placeholder(placeholder));
+                }
+                '[' => {
+                    let inner = self.segments(Some(offset))?;
+                    if inner.iter().any(|s| !matches!(s, Segment::Literal(_))) {
+                        flush(&mut literal, &mut segments);
+                        segments.push(Segment::Optional(inner));
+                    } else {
+                        // Brackets around plain text, such as "[Group]", are kept as-is.
+                        literal.push('[');
+                        inner.iter().for_each(|s| if let Segment::Literal(t) = s { literal.push_str(t) });
+                        literal.push(']');
+                    }
+                }
+                ']' if open.is_some() => {
+                    flush(&mut literal, &mut segments);
+                    return Ok(segments);
+                }
+                _ => {
+                    return Err(RenamerError::InvalidTemplate(format!("unmatched '{}' at position {}", c, offset)));
+                }
+            }
+        }
+        if let Some(open) = open {
+            return Err(RenamerError::InvalidTemplate(format!("unclosed '[' at position {}", open)));
+        }
+        literal.push_str(self.rest);
+        self.rest = "";
+        flush(&mut literal, &mut segments);
+        Ok(segments)
+    }
+}
+
+/// Moves pending literal text into a segment.
+fn flush(literal: &mut String, segments: &mut Vec<Segment>) {
+    if !literal.is_empty() {
+        segments.push(Segment::Literal(std::mem::take(literal)));
+    }
+}
+
+fn collect_placeholders<'a>(segments: &'a [Segment], found: &mut Vec<&'a Placeholder>) {
+    for segment in segments {
+        match segment {
+            Segment::Literal(_) => {}
+            Segment::Placeholder(p) => found.push(p),
+            Segment::Optional(inner) => collect_placeholders(inner, found),
+        }
+    }
+}
+
+fn render_segments(
+    segments: &[Segment],
+    caps: &Captures,
+    counter: Option<i64>,
+    out: &mut String,
+) -> Result<(), RenamerError> {
+    for segment in segments {
+        match segment {
+            Segment::Literal(text) => out.push_str(text),
+            Segment::Placeholder(p) => out.push_str(&p.render(caps, counter)?),
+            Segment::Optional(inner) => {
+                let complete = inner.iter().all(|s| match s {
+                    Segment::Placeholder(p) => p.value(caps, counter).is_some(),
+                    _ => true,
+                });
+                if complete {
+                    render_segments(inner, caps, counter, out)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+impl Placeholder {
+    /// The captured value (or the counter for `{n}`), or the default if the group did
+    /// not match or matched nothing.
+    fn value<'a>(&'a self, caps: &Captures<'a>, counter: Option<i64>) -> Option<Cow<'a, str>> {
+        match caps.name(&self.name) {
+            Some(m) if !m.as_str().is_empty() => Some(Cow::Borrowed(m.as_str())),
+            None if self.name == COUNTER && counter.is_some() => counter.map(|n| Cow::Owned(n.to_string())),
+            _ => self.default.as_deref().map(Cow::Borrowed),
+        }
+    }
+
+    /// Renders the placeholder: value or default, then arithmetic, filters and padding.
+    fn render(&self, caps: &Captures, counter: Option<i64>) -> Result<String, RenamerError> {
+        let value = self.value(caps, counter).unwrap_or_default();
+        let value = if self.ops.is_empty() {
+            value.to_string()
+        } else {
+            let not_numeric = || RenamerError::NotNumeric { name: self.name.clone(), value: value.to_string() };
+            let number = value.parse::<i64>().map_err(|_| not_numeric())?;
+            self.ops
+                .iter()
+                .try_fold(number, |acc, &(op, rhs)| op.apply(acc, rhs))
+                .ok_or_else(not_numeric)?
+                .to_string()
+        };
+        let value = self.filters.iter().fold(value, |v, f| f.apply(&v));
+        Ok(format_value(&value, self.width))
+    }
+}
+
+impl FromStr for Template {
+    type Err = RenamerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Template::parse(s)
+    }
+}
+
+impl fmt::Display for Template {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// Parses the inside of a `{...}` placeholder.
+fn parse_placeholder(body: &str) -> Result<Placeholder, RenamerError> {
+    let mut pipeline = body.split('|');
+    let head = pipeline.next().unwrap_or_default();
+    let filters = pipeline.map(Filter::parse).collect::<Result<Vec<_>, _>>()?;
+    let (name, width) = match head.split_once(':') {
+        Some((name, width)) => (name, Some(width)),
+        None => (head, None),
+    };
+    let (expr, default) = match name.split_once('?') {
+        Some((expr, default)) => (expr, Some(default.to_string())),
+        None => (name, None),
+    };
+    let name_end = expr.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(expr.len());
+    let (name, mut arithmetic) = expr.split_at(name_end);
+    if name.is_empty() {
+        return Err(RenamerError::InvalidTemplate(format!("invalid placeholder name in {{{}}}", body)));
+    }
+    let mut ops = Vec::new();
+    while let Some(op) = arithmetic.chars().next() {
+        let op = Op::from_char(op)
+            .ok_or_else(|| RenamerError::InvalidTemplate(format!("invalid placeholder name in {{{}}}", body)))?;
+        arithmetic = &arithmetic[1..];
+        let end = arithmetic.find(|c: char| !c.is_ascii_digit()).unwrap_or(arithmetic.len());
+        let operand = arithmetic[..end].parse::<i64>().map_err(|_| {
+            RenamerError::InvalidTemplate(format!("expected a number after the operator in {{{}}}", body))
+        })?;
+        if op == Op::Div && operand == 0 {
+            return Err(RenamerError::InvalidTemplate(format!("division by zero in {{{}}}", body)));
+        }
+        ops.push((op, operand));
+        arithmetic = &arithmetic[end..];
+    }
+    let width = width
+        .map(|w| {
+            w.parse::<usize>()
+                .map_err(|_| RenamerError::InvalidTemplate(format!("invalid width '{}' in {{{}}}", w, body)))
+        })
+        .transpose()?;
+    Ok(Placeholder { name: name.to_string(), ops, default, width, filters })
+}
+
+/// Upper-cases the first letter of every whitespace-separated word and lower-cases the rest.
+fn title_case(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut word_start = true;
+    for c in value.chars() {
+        if word_start {
+            out.extend(c.to_uppercase());
+        } else {
+            out.extend(c.to_lowercase());
+        }
+        word_start = c.is_whitespace();
+    }
+    out
+}
+
+/// Lower-cases the value and joins its alphanumeric runs with single dashes.
+fn slug(value: &str) -> String {
+    value
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Pads a captured value to `width`: numeric values with leading zeros, others with spaces.
+fn format_value(value: &str, width: Option<usize>) -> String {
+    match width {
+        Some(width) => match (value.parse::<usize>(), value.parse::<i64>()) {
+            (Ok(num_value), _) => format!("{:0width$}", num_value, width = width),
+            (_, Ok(negative)) => format!("{:0width$}", negative, width = width),
+            _ => format!("{:width$}", value, width = width),
+        },
+        None => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(template: &str, re: &str, name: &str) -> String {
+        let re = Regex::new(re).unwrap();
+        Template::parse(template).unwrap().render(&re.captures(name).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_parse_segments() {
+        let template = Template::parse("{title} - S{season:02}").unwrap();
+        assert_eq!(template.placeholders().collect::<Vec<_>>(), vec!["title", "season"]);
+        assert_eq!(template.to_string(), "{title} - S{season:02}");
+    }
+
+    #[test]
+    fn test_escaped_braces() {
+        assert_eq!(render("{{{n}}}", r"(?P<n>\d+)", "7"), "{7}");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for bad in ["{title", "title}", "{}", "{ti tle}", "{title:x}"] {
+            assert!(
+                matches!(Template::parse(bad), Err(RenamerError::InvalidTemplate(_))),
+                "{} should not parse",
+                bad
+            );
+        }
+    }
+
+    #[test]
+    fn test_filters() {
+        let re = r"(?P<title>.+)";
+        assert_eq!(render("{title|lower}", re, "My Show"), "my show");
+        assert_eq!(render("{title|upper}", re, "My Show"), "MY SHOW");
+        assert_eq!(render("{title|title_case}", re, "the BIG show"), "The Big Show");
+        assert_eq!(render("{title|slug}", re, "The Big Show: Part 2!"), "the-big-show-part-2");
+        assert_eq!(render("({title|trim})", re, "  padded  "), "(padded)");
+        assert_eq!(render("{title|replace:_: }", re, "my_show_name"), "my show name");
+        assert_eq!(render("{title|replace:.:}", re, "a.b.c"), "abc");
+    }
+
+    #[test]
+    fn test_filters_chain_before_padding() {
+        let re = r"(?P<title>.+)";
+        assert_eq!(render("{title:8|replace:.: |title_case}|", re, "my.show"), "My Show |");
+    }
+
+    #[test]
+    fn test_filter_errors() {
+        for bad in ["{title|shout}", "{title|lower:x}", "{title|replace:_}", "{title|replace::x}"] {
+            assert!(
+                matches!(Template::parse(bad), Err(RenamerError::InvalidTemplate(_))),
+                "{} should not parse",
+                bad
+            );
+        }
+    }
+
+    #[test]
+    fn test_defaults() {
+        let re = r"(?:S(?P<season>\d+))?E(?P<episode>\d+)";
+        assert_eq!(render("S{season?1:02}E{episode:02}", re, "E5"), "S01E05");
+        assert_eq!(render("S{season?1:02}E{episode:02}", re, "S3E5"), "S03E05");
+        assert_eq!(render("{missing?Unknown|upper}", re, "E5"), "UNKNOWN");
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let re = r"(?P<episode>\d+)";
+        assert_eq!(render("E{episode-25:02}", re, "26"), "E01");
+        assert_eq!(render("E{episode+1}", re, "09"), "E10");
+        assert_eq!(render("{episode*2-1:03}", re, "5"), "009");
+        assert_eq!(render("{episode/2}", re, "7"), "3");
+        assert_eq!(render("{episode-30:03}", re, "26"), "-04");
+        assert_eq!(render("{missing+1?0:02}", r"(?P<missing>x)?", ""), "01");
+    }
+
+    #[test]
+    fn test_arithmetic_errors() {
+        let re = Regex::new(r"(?P<title>[a-z]+)").unwrap();
+        let template = Template::parse("{title+1}").unwrap();
+        let err = template.render(&re.captures("abc").unwrap()).unwrap_err();
+        assert!(matches!(err, RenamerError::NotNumeric { name, value } if name == "title" && value == "abc"));
+        for bad in ["{episode+}", "{episode+x}", "{episode/0}", "{episode%2}"] {
+            assert!(Template::parse(bad).is_err(), "{} should not parse", bad);
+        }
+    }
+
+    #[test]
+    fn test_counter() {
+        let re = Regex::new(r"IMG_(?P<id>\d+)").unwrap();
+        let template = Template::parse("Photo {n:03}[ ({id})]").unwrap();
+        assert!(template.check_groups(&re).is_ok());
+        assert!(template.uses_counter());
+        let caps = re.captures("IMG_4821.jpg").unwrap();
+        assert_eq!(template.render_with(&caps, Some(7)).unwrap(), "Photo 007 (4821)");
+        // Without a counter, `{n}` is missing like any unmatched group.
+        assert_eq!(Template::parse("Photo {n}").unwrap().render(&caps).unwrap(), "Photo ");
+
+        // A group named `n` takes precedence over the counter.
+        let re = Regex::new(r"(?P<n>\d+)").unwrap();
+        let template = Template::parse("{n}").unwrap();
+        assert_eq!(template.render_with(&re.captures("42").unwrap(), Some(1)).unwrap(), "42");
+    }
+
+    #[test]
+    fn test_optional_segments() {
+        let re = r"(?P<title>[a-z]+)(?:-(?P<part>\d+))?";
+        let template = "{title}[ - Part {part}]";
+        assert_eq!(render(template, re, "show-2"), "show - Part 2");
+        assert_eq!(render(template, re, "show"), "show");
+        // Plain brackets and escaped brackets stay literal.
+        assert_eq!(render("[Group] {title}", re, "show"), "[Group] show");
+        assert_eq!(render("[[{title}]]", re, "show"), "[show]");
+        // Nested optional segments decide independently.
+        assert_eq!(render("{title}[ ({title}[ {part}])]", re, "show"), "show (show)");
+    }
+
+    #[test]
+    fn test_bracket_errors() {
+        for bad in ["[{title}", "{title}]", "[ [{title}]"] {
+            assert!(
+                matches!(Template::parse(bad), Err(RenamerError::InvalidTemplate(_))),
+                "{} should not parse",
+                bad
+            );
+        }
+    }
+
+    #[test]
+    fn test_check_groups() {
+        let re = Regex::new(r"S(?P<season>\d+)E(?P<episode>\d+)").unwrap();
+        assert!(Template::parse("S{season}E{episode}").unwrap().check_groups(&re).is_ok());
+        let err = Template::parse("{title} - S{season}").unwrap().check_groups(&re).unwrap_err();
+        assert!(matches!(err, RenamerError::UnknownPlaceholder(name) if name == "title"));
+        assert!(Template::parse("{title?Show} - S{season}").unwrap().check_groups(&re).is_ok());
+        assert!(Template::parse("S{season}[ {part}][ - {title}[ {n}] ]").unwrap().check_groups(&re).is_ok());
+        assert!(Template::parse("{title}[ {part}]").unwrap().check_groups(&re).is_err());
+    }
+
+    #[test]
+    fn test_render_padding_and_missing_groups() {
+        let re = r"(?P<title>[a-z]+)(?:-(?P<part>\d+))?";
+        assert_eq!(render("{title:6}|{part:03}", re, "abc-7"), "abc   |007");
+        assert_eq!(render("{title}-{part}", re, "abc"), "abc-");
+    }
+}
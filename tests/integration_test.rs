@@ -1,9 +1,19 @@
-use renamer::{Cli, transform_filename, should_process_file, merge_config};
+use renamer::{Cli, transform_filename, should_process_file};
+use clap::Parser;
+use renamer::config::{env_layers, load_config, merge_config_with, AppConfig, Settings, Source};
+use renamer::plan::{ConflictPolicy, SortKey};
+use renamer::preset::Preset;
 use regex::Regex;
 use tempfile::{tempdir, NamedTempFile};
 use std::io::Write;
 use std::path::PathBuf;
 
+/// Merges the settings of `cli` ignoring the process environment and the user config,
+/// so the tests do not depend on the machine they run on.
+fn merge(cli: &mut Cli) -> anyhow::Result<Settings> {
+    merge_config_with(cli, |_| None, None)
+}
+
 // Add a music file example test
 #[test]
 fn test_transform_music_file() {
@@ -63,18 +73,37 @@ fn test_depth_option() {
 
     // Create a dummy CLI configuration with depth=2.
     let cli = Cli {
+        command: None,
         config: None,
-        directory: base_path.to_path_buf(),
-        current_pattern: "(.+)".to_string(),
-        new_pattern: "$1".to_string(),
+        config_format: None,
+        profile: None,
+        no_discovery: false,
+        show_config: false,
+        preset: None,
+        directory: Some(base_path.to_path_buf()),
+        current_pattern: Some("(.+)".to_string()),
+        new_pattern: Some("$1".to_string()),
+        rules: vec![],
         file_types: vec!["txt".to_string()],
         dry_run: true,
-        depth: 2,
+        on_conflict: None,
+        atomic: false,
+        sort: None,
+        counter_start: None,
+        counter_step: None,
+        counter_sort: None,
+        edit: false,
+        interactive: false,
+        depth: Some(2),
+        format: None,
+        color: None,
+        plan_out: None,
+        journal: None,
     };
 
     // Count the number of files processed using WalkDir with max_depth as specified.
     let mut count = 0;
-    let walker = walkdir::WalkDir::new(&cli.directory).max_depth(cli.depth).into_iter();
+    let walker = walkdir::WalkDir::new(cli.directory()).max_depth(cli.depth()).into_iter();
     for entry in walker.filter_map(|e| e.ok()) {
         let path = entry.path();
         if path.is_file() && should_process_file(path, &cli.file_types) {
@@ -94,28 +123,403 @@ fn test_config_file_merging() {
     writeln!(config_file, r#"new_pattern = "Configured - C{{season:02}}D{{episode:02}}""#).unwrap();
     writeln!(config_file, r#"file_types = ["mp4", "avi"]"#).unwrap();
     writeln!(config_file, r#"dry_run = false"#).unwrap();
+    writeln!(config_file, r#"atomic = true"#).unwrap();
+    writeln!(config_file, r#"on_conflict = "skip""#).unwrap();
+    writeln!(config_file, r#"sort = "mtime""#).unwrap();
+    writeln!(config_file, r#"counter_start = 100"#).unwrap();
+    writeln!(config_file, r#"counter_sort = "size""#).unwrap();
     // Removed default_season and title
     writeln!(config_file, r#"depth = 3"#).unwrap();
+    writeln!(config_file, r#"[[rules]]"#).unwrap();
+    writeln!(config_file, r#"name = "1x02""#).unwrap();
+    writeln!(config_file, r#"pattern = "(?P<season>\\d+)x(?P<episode>\\d+)""#).unwrap();
+    writeln!(config_file, r#"template = "S{{season:02}}E{{episode:02}}""#).unwrap();
 
-    // Create a CLI instance with empty values and set the config field.
+    // Create a CLI instance with no options given and set the config field.
     let mut cli = Cli {
+        command: None,
         config: Some(PathBuf::from(config_file.path())),
-        directory: "".into(),
-        current_pattern: "".into(),
-        new_pattern: "".into(),
+        config_format: None,
+        profile: None,
+        no_discovery: true,
+        show_config: false,
+        preset: None,
+        directory: None,
+        current_pattern: None,
+        new_pattern: None,
+        rules: vec![],
         file_types: vec![],
-        dry_run: true, // This should be overridden by config.
-        depth: 1,
+        dry_run: false, // Not given, so the config decides.
+        on_conflict: None,
+        atomic: false,
+        sort: None,
+        counter_start: None,
+        counter_step: None,
+        counter_sort: None,
+        edit: false,
+        interactive: false,
+        depth: None,
+        format: None,
+        color: None,
+        plan_out: None,
+        journal: None,
     };
 
     // Merge configuration from the temporary file.
-    merge_config(&mut cli).expect("Failed to merge config");
+    merge(&mut cli).expect("Failed to merge config");
 
     // Assert that CLI fields have been updated according to the config file.
-    assert_eq!(cli.directory, PathBuf::from("/configured/dir"));
-    assert_eq!(cli.current_pattern, "C(?P<season>\\d+)D(?P<episode>\\d+)");
-    assert_eq!(cli.new_pattern, "Configured - C{season:02}D{episode:02}");
+    assert_eq!(cli.directory(), PathBuf::from("/configured/dir"));
+    assert_eq!(cli.current_pattern.as_deref(), Some("C(?P<season>\\d+)D(?P<episode>\\d+)"));
+    assert_eq!(cli.new_pattern(), "Configured - C{season:02}D{episode:02}");
     assert_eq!(cli.file_types, vec!["mp4".to_string(), "avi".to_string()]);
-    assert_eq!(cli.dry_run, false);
-    assert_eq!(cli.depth, 3);
-}
\ No newline at end of file
+    assert_eq!(cli.rules.len(), 1);
+    assert_eq!(cli.rules[0].name.as_deref(), Some("1x02"));
+    assert_eq!(cli.rules[0].template, "S{season:02}E{episode:02}");
+    assert!(!cli.dry_run);
+    assert!(cli.atomic);
+    assert_eq!(cli.on_conflict, Some(ConflictPolicy::Skip));
+    assert_eq!(cli.sort, Some(SortKey::Mtime));
+    assert_eq!(cli.counter_start, Some(100));
+    assert_eq!(cli.counter_step, Some(1)); // Built-in default.
+    assert_eq!(cli.counter_sort, Some(SortKey::Size));
+    assert_eq!(cli.depth(), 3);
+}
+
+#[test]
+fn test_config_precedence() {
+    let mut config_file = NamedTempFile::new().unwrap();
+    writeln!(config_file, "dry_run = false\ndepth = 3\nsort = \"size\"\n[profile.deep]\ndepth = 5").unwrap();
+    let path = config_file.path().to_str().unwrap();
+
+    // Options given on the command line win even when they equal the defaults.
+    let mut cli = Cli::parse_from(["renamer", "--no-discovery", "--config", path, "--depth", "1", "--dry-run"]);
+    let settings = merge(&mut cli).expect("Failed to merge config");
+    assert_eq!(cli.depth(), 1);
+    assert!(cli.dry_run);
+    assert_eq!(settings.source("depth"), Some(&Source::Cli));
+    assert_eq!(settings.source("sort"), Some(&Source::ConfigFile(config_file.path().into())));
+    assert_eq!(settings.source("atomic"), Some(&Source::Default));
+    assert_eq!(settings.source("current_pattern"), None);
+
+    let mut cli = Cli::parse_from(["renamer", "--no-discovery", "--config", path, "-p", "deep"]);
+    let settings = merge(&mut cli).expect("Failed to merge config");
+    assert_eq!(cli.depth(), 5);
+    assert!(!cli.dry_run);
+    let shown = settings.to_string();
+    assert!(shown.contains("depth = 5  # profile 'deep' in "), "{}", shown);
+    assert!(shown.contains("atomic = false  # default"), "{}", shown);
+    assert!(shown.contains("# current_pattern is not set"), "{}", shown);
+}
+
+#[test]
+fn test_env_overrides() {
+    let env = |var: &str| match var {
+        "RENAMER_DEPTH" => Some("4".to_string()),
+        "RENAMER_SORT" => Some("Size".to_string()),
+        "RENAMER_RULES" => Some("(?P<season>\\d+)x(?P<episode>\\d+)=>S{season:02}E{episode:02}\nEp (?P<episode>\\d+)=>E{episode}".to_string()),
+        "RENAMER_FORMAT" => Some(String::new()),
+        _ => None,
+    };
+    let layers = env_layers(env).unwrap();
+    assert_eq!(layers.len(), 3);
+    let rules = layers[0].1.rules.as_ref().unwrap();
+    assert_eq!(rules.len(), 2);
+    assert_eq!(rules[1].template, "E{episode}");
+
+    // Environment variables override config files, the command line overrides both.
+    let mut settings = Settings::default();
+    settings.push(Source::Default, AppConfig::defaults());
+    settings.push(Source::ConfigFile("c.toml".into()), AppConfig { depth: Some(2), sort: Some(SortKey::Name), ..AppConfig::default() });
+    for (source, layer) in layers {
+        settings.push(source, layer);
+    }
+    settings.push(Source::Cli, AppConfig { depth: Some(1), ..AppConfig::default() });
+    assert_eq!(settings.values.depth, Some(1));
+    assert_eq!(settings.values.sort, Some(SortKey::Size));
+    assert_eq!(settings.source("sort"), Some(&Source::Env("RENAMER_SORT".to_string())));
+    assert_eq!(settings.source("format"), None);
+
+    let err = env_layers(|var| (var == "RENAMER_DRY_RUN").then(|| "maybe".to_string())).unwrap_err().to_string();
+    assert!(err.contains("RENAMER_DRY_RUN") && err.contains("maybe"), "{}", err);
+}
+
+#[test]
+fn test_config_validation() {
+    let check = |toml: &str| {
+        let mut config_file = NamedTempFile::new().unwrap();
+        write!(config_file, "{}", toml).unwrap();
+        load_config(config_file.path(), None).map(|_| ()).map_err(|e| e.to_string())
+    };
+    let err = check("depth = 2\ndefault_season = \"1\"\n").unwrap_err();
+    assert!(err.contains("line 2, column 1") && err.contains("unknown field `default_season`"), "{}", err);
+    let err = check("file_types = [\"mkv\"]\ndepth = \"two\"\n").unwrap_err();
+    assert!(err.contains("line 2, column 9"), "{}", err);
+    let err = check("[profile.anime]\nsort = \"random\"\n").unwrap_err();
+    assert!(err.contains("line 2, column 8"), "{}", err);
+    let err = check("current_pattern = 'S(?P<season>\\d+'\n").unwrap_err();
+    assert!(err.contains("current_pattern: invalid regex pattern"), "{}", err);
+    let err = check("current_pattern = '(?P<episode>\\d+)'\nnew_pattern = \"{title} {episode}\"\n").unwrap_err();
+    assert!(err.contains("new_pattern") && err.contains("{title}"), "{}", err);
+    let err = check("[profile.x]\n[[profile.x.rules]]\nname = \"bad\"\npattern = '(?P<a>.)'\ntemplate = \"{b}\"\n").unwrap_err();
+    assert!(err.contains("profile.x.rules: Invalid rule bad"), "{}", err);
+
+    // The shipped example configuration is valid.
+    load_config(&std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("config.toml"), None).unwrap();
+
+    // A pattern and a template from different layers are checked together.
+    let mut config_file = NamedTempFile::new().unwrap();
+    writeln!(config_file, "current_pattern = '(?P<episode>\\d+)'").unwrap();
+    let path = config_file.path().to_str().unwrap();
+    let mut cli = Cli::parse_from(["renamer", "--no-discovery", "--config", path, "-n", "{show} {episode}"]);
+    let err = merge(&mut cli).unwrap_err().to_string();
+    assert!(err.contains("{show}") && err.contains("current_pattern from config file") && err.contains("new_pattern from command line"), "{}", err);
+}
+
+#[test]
+fn test_yaml_and_json_config() {
+    let dir = tempdir().unwrap();
+    let yaml = dir.path().join("renamer.yml");
+    std::fs::write(&yaml, r#"
+current_pattern: 'S(?P<season>\d+)E(?P<episode>\d+)'
+new_pattern: "Show - S{season:02}E{episode:02}"
+file_types: [mkv, mp4]
+depth: 3
+rules:
+  - name: 1x02
+    pattern: '(?P<season>\d+)x(?P<episode>\d+)'
+    template: "Show - S{season:02}E{episode:02}"
+profile:
+  deep:
+    depth: 5
+"#).unwrap();
+    let json = dir.path().join("renamer.json");
+    std::fs::write(&json, r#"{
+  "current_pattern": "S(?P<season>\\d+)E(?P<episode>\\d+)",
+  "new_pattern": "Show - S{season:02}E{episode:02}",
+  "file_types": ["mkv", "mp4"],
+  "depth": 3,
+  "rules": [{"name": "1x02", "pattern": "(?P<season>\\d+)x(?P<episode>\\d+)", "template": "Show - S{season:02}E{episode:02}"}],
+  "profile": {"deep": {"depth": 5}}
+}"#).unwrap();
+    // Without a known extension the format must be given.
+    let conf = dir.path().join("renamer.conf");
+    std::fs::copy(&json, &conf).unwrap();
+
+    for args in [
+        vec!["--config", yaml.to_str().unwrap()],
+        vec!["--config", json.to_str().unwrap()],
+        vec!["--config", conf.to_str().unwrap(), "--config-format", "json"],
+    ] {
+        let mut cli = Cli::parse_from(["renamer", "--no-discovery", "-p", "deep"].into_iter().chain(args));
+        merge(&mut cli).expect("Failed to merge config");
+        assert_eq!(cli.current_pattern.as_deref(), Some(r"S(?P<season>\d+)E(?P<episode>\d+)"));
+        assert_eq!(cli.file_types, vec!["mkv".to_string(), "mp4".to_string()]);
+        assert_eq!(cli.rules[0].name.as_deref(), Some("1x02"));
+        assert_eq!(cli.depth(), 5);
+    }
+    assert!(load_config(&conf, None).is_err());
+
+    // Validation is the same for every format.
+    std::fs::write(&yaml, "depth: 2\ntitle: My Show\n").unwrap();
+    let err = load_config(&yaml, None).unwrap_err().to_string();
+    assert!(err.contains("line 2, column 1") && err.contains("unknown field `title`"), "{}", err);
+    std::fs::write(&json, "{\n  \"depth\": \"two\"\n}").unwrap();
+    let err = load_config(&json, None).unwrap_err().to_string();
+    assert!(err.contains("line 2") && err.contains("invalid type"), "{}", err);
+    std::fs::write(&json, r#"{"current_pattern": "S(?P<season>"}"#).unwrap();
+    let err = load_config(&json, None).unwrap_err().to_string();
+    assert!(err.contains("current_pattern: invalid regex pattern"), "{}", err);
+}
+
+#[test]
+fn test_presets() {
+    let mut cli = Cli::parse_from(["renamer", "--no-discovery", "--preset", "tv-1x02"]);
+    let settings = merge(&mut cli).expect("Failed to merge config");
+    assert_eq!(cli.current_pattern.as_deref(), Some(Preset::find("tv-1x02").unwrap().pattern));
+    assert_eq!(settings.source("new_pattern"), Some(&Source::Preset("tv-1x02".to_string())));
+
+    // Options override the preset's pattern and template.
+    let mut cli = Cli::parse_from(["renamer", "--no-discovery", "--preset", "tv-1x02", "-n", "{title} {season}x{episode}"]);
+    let settings = merge(&mut cli).expect("Failed to merge config");
+    assert_eq!(cli.new_pattern(), "{title} {season}x{episode}");
+    assert_eq!(settings.source("current_pattern"), Some(&Source::Preset("tv-1x02".to_string())));
+
+    // Config files select presets, redefine them and add their own.
+    let mut config_file = NamedTempFile::new().unwrap();
+    write!(config_file, r#"
+preset = "tv-sxxeyy"
+
+[presets.tv-sxxeyy]
+pattern = 'S(?P<season>\d+)E(?P<episode>\d+)'
+template = "Episode {{episode}}"
+
+[presets.scans]
+pattern = '^scan'
+template = "Page {{n:02}}"
+"#).unwrap();
+    let path = config_file.path().to_str().unwrap();
+    let mut cli = Cli::parse_from(["renamer", "--no-discovery", "--config", path]);
+    let settings = merge(&mut cli).expect("Failed to merge config");
+    assert_eq!(cli.new_pattern(), "Episode {episode}");
+    assert_eq!(settings.values.presets.as_ref().unwrap().len(), 2);
+    let mut cli = Cli::parse_from(["renamer", "--no-discovery", "--config", path, "--preset", "scans"]);
+    merge(&mut cli).expect("Failed to merge config");
+    assert_eq!(cli.current_pattern.as_deref(), Some("^scan"));
+
+    // A preset given on the command line wins over the patterns of discovered and user
+    // configs, while a pattern set next to the preset in a file overrides it.
+    let dir = tempdir().unwrap();
+    let user = dir.path().join("user.toml");
+    std::fs::write(&user, "new_pattern = \"{episode}\"\n").unwrap();
+    std::fs::write(dir.path().join(".renamer.toml"), "current_pattern = '(?P<episode>\\d+)'\n").unwrap();
+    let dir_arg = dir.path().to_str().unwrap();
+    let mut cli = Cli::parse_from(["renamer", "-d", dir_arg, "--preset", "tv-1x02"]);
+    let settings = merge_config_with(&mut cli, |_| None, Some(&user)).expect("Failed to merge config");
+    assert_eq!(settings.source("current_pattern"), Some(&Source::Preset("tv-1x02".to_string())));
+    assert_eq!(settings.source("new_pattern"), Some(&Source::Preset("tv-1x02".to_string())));
+    std::fs::write(dir.path().join(".renamer.toml"), "preset = \"tv-1x02\"\ncurrent_pattern = '(?P<title>.+) (?P<season>\\d+)-(?P<episode>\\d+)'\n").unwrap();
+    let mut cli = Cli::parse_from(["renamer", "-d", dir_arg]);
+    merge_config_with(&mut cli, |_| None, Some(&user)).expect("Failed to merge config");
+    assert_eq!(cli.current_pattern.as_deref(), Some(r"(?P<title>.+) (?P<season>\d+)-(?P<episode>\d+)"));
+    assert_eq!(cli.new_pattern(), Preset::find("tv-1x02").unwrap().template);
+
+    let mut cli = Cli::parse_from(["renamer", "--no-discovery", "--config", path, "--preset", "tv"]);
+    let err = merge(&mut cli).unwrap_err().to_string();
+    assert!(err.contains("Unknown preset 'tv'") && err.contains("scans") && err.contains("date-iso"), "{}", err);
+}
+
+#[test]
+fn test_default_template_without_title_group() {
+    let mut cli = Cli::parse_from(["renamer", "--no-discovery", "-c", r"S(?P<season>\d+)E(?P<episode>\d+)"]);
+    merge(&mut cli).expect("Failed to merge config");
+    let rule = renamer::rule::Rule::compile(&cli.rule_specs()[0], 0).unwrap();
+    assert_eq!(renamer::renamer::transform_with_template("show_S1E2.mkv", &rule.template, &rule.pattern).unwrap(), "S01E02.mkv");
+
+    // Config files are checked by the same rule, whether or not the template is the default.
+    let mut config_file = NamedTempFile::new().unwrap();
+    writeln!(config_file, "current_pattern = 'S(?P<season>\\d+)E(?P<episode>\\d+)'\nnew_pattern = \"S{{season:02}}E{{episode:02}}[ - {{title}}]\"").unwrap();
+    load_config(config_file.path(), None).unwrap();
+
+    let rule = renamer::rule::Rule::compile(&r"(?P<title>.+)_S(?P<season>\d+)E(?P<episode>\d+)=>[{title} - ]S{season:02}E{episode:02}".parse().unwrap(), 0).unwrap();
+    assert_eq!(renamer::renamer::transform_with_template("show_S1E2.mkv", &rule.template, &rule.pattern).unwrap(), "show - S01E02.mkv");
+}
+
+#[test]
+fn test_config_profiles() {
+    let mut config_file = NamedTempFile::new().unwrap();
+    write!(config_file, r#"
+current_pattern = 'S(?P<season>\d+)E(?P<episode>\d+)'
+new_pattern = "{{title?Show}} - S{{season:02}}E{{episode:02}}"
+file_types = ["mkv"]
+depth = 2
+
+[profile.anime]
+current_pattern = '\[(?P<group>[^]]+)\] (?P<title>.+) - (?P<episode>\d+)'
+new_pattern = "{{title}} - S01E{{episode:02}}"
+depth = 4
+
+[profile.photos]
+current_pattern = 'IMG_(?P<date>\d{{8}})'
+new_pattern = "{{date}} {{n:03}}"
+file_types = ["jpg", "heic"]
+"#).unwrap();
+    let path = config_file.path().to_str().unwrap();
+
+    let mut cli = Cli::parse_from(["renamer", "--config", path, "--profile", "anime"]);
+    merge(&mut cli).expect("Failed to merge config");
+    assert_eq!(cli.current_pattern.as_deref(), Some(r"\[(?P<group>[^]]+)\] (?P<title>.+) - (?P<episode>\d+)"));
+    // Values the profile does not set come from the top level.
+    assert_eq!(cli.file_types, vec!["mkv".to_string()]);
+    assert_eq!(cli.depth(), 4);
+
+    let mut cli = Cli::parse_from(["renamer", "--config", path, "-p", "photos"]);
+    merge(&mut cli).expect("Failed to merge config");
+    assert_eq!(cli.file_types, vec!["jpg".to_string(), "heic".to_string()]);
+    assert_eq!(cli.depth(), 2);
+
+    let mut cli = Cli::parse_from(["renamer", "--config", path]);
+    merge(&mut cli).expect("Failed to merge config");
+    assert_eq!(cli.current_pattern.as_deref(), Some(r"S(?P<season>\d+)E(?P<episode>\d+)"));
+
+    let mut cli = Cli::parse_from(["renamer", "--config", path, "--profile", "music"]);
+    let err = merge(&mut cli).unwrap_err().to_string();
+    assert!(err.contains("Unknown profile 'music'") && err.contains("anime, photos"), "{}", err);
+
+    let mut cli = Cli::parse_from(["renamer", "--no-discovery", "--profile", "anime"]);
+    assert!(merge(&mut cli).is_err());
+}
+
+#[test]
+fn test_config_discovery() {
+    let root = tempdir().unwrap();
+    let show = root.path().join("show");
+    let season = show.join("season1");
+    std::fs::create_dir_all(&season).unwrap();
+    let user = root.path().join("user.toml");
+    std::fs::write(&user, "file_types = [\"avi\"]\ndepth = 7\nsort = \"size\"\n").unwrap();
+    std::fs::write(root.path().join(".renamer.toml"), "file_types = [\"mkv\"]\ndepth = 5\n").unwrap();
+    std::fs::write(show.join(".renamer.toml"), "depth = 3\n[profile.hd]\ndepth = 9\n").unwrap();
+
+    // Lowest precedence first: user config, then the root down to the target directory.
+    let found = renamer::config::discover_configs(&season, Some(&user));
+    assert!(found.ends_with(&[user.clone(), root.path().join(".renamer.toml"), show.join(".renamer.toml")]));
+    assert_eq!(found[0], user);
+
+    let season_dir = season.to_str().unwrap();
+    let mut cli = Cli::parse_from(["renamer", "-d", season_dir]);
+    let settings = merge_config_with(&mut cli, |_| None, Some(&user)).expect("Failed to merge config");
+    assert_eq!(cli.depth(), 3);
+    assert_eq!(cli.file_types, vec!["mkv".to_string()]);
+    assert_eq!(cli.sort, Some(SortKey::Size));
+    assert_eq!(settings.source("sort"), Some(&Source::UserConfig(user.clone())));
+
+    // An explicit --config overrides the discovered files, profiles are merged by name.
+    let mut config_file = NamedTempFile::new().unwrap();
+    writeln!(config_file, "depth = 4").unwrap();
+    let path = config_file.path().to_str().unwrap();
+    let mut cli = Cli::parse_from(["renamer", "-d", season_dir, "--config", path]);
+    merge(&mut cli).expect("Failed to merge config");
+    assert_eq!(cli.depth(), 4);
+    let mut cli = Cli::parse_from(["renamer", "-d", season_dir, "--config", path, "-p", "hd"]);
+    merge(&mut cli).expect("Failed to merge config");
+    assert_eq!(cli.depth(), 9);
+
+    // `..` is resolved before walking up, so a sibling does not inherit the config of the
+    // directory the path goes through.
+    let sibling = root.path().join("movies");
+    std::fs::create_dir(&sibling).unwrap();
+    let through_show = season.join("..").join("..").join("movies");
+    let found = renamer::config::discover_configs(&through_show, None);
+    assert!(!found.contains(&show.join(".renamer.toml")), "{:?}", found);
+    let mut cli = Cli::parse_from(["renamer", "-d", through_show.to_str().unwrap()]);
+    merge(&mut cli).expect("Failed to merge config");
+    assert_eq!(cli.depth(), 5);
+
+    // The target directory may come from the environment or the --config file.
+    let mut cli = Cli::parse_from(["renamer"]);
+    let env = |var: &str| (var == "RENAMER_DIRECTORY").then(|| season_dir.to_string());
+    merge_config_with(&mut cli, env, None).expect("Failed to merge config");
+    assert_eq!(cli.directory(), season);
+    assert_eq!(cli.depth(), 3);
+    let mut config_file = NamedTempFile::new().unwrap();
+    writeln!(config_file, "directory = {:?}", show).unwrap();
+    let path = config_file.path().to_str().unwrap();
+    let mut cli = Cli::parse_from(["renamer", "--config", path]);
+    merge(&mut cli).expect("Failed to merge config");
+    assert_eq!(cli.directory(), show);
+    assert_eq!(cli.file_types, vec!["mkv".to_string()]);
+    let mut cli = Cli::parse_from(["renamer", "--config", path, "-p", "hd"]);
+    merge(&mut cli).expect("Failed to merge config");
+    assert_eq!(cli.depth(), 9);
+
+    let mut cli = Cli::parse_from(["renamer", "-d", season_dir, "--no-discovery"]);
+    merge(&mut cli).expect("Failed to merge config");
+    assert_eq!(cli.depth(), 1);
+    assert!(cli.file_types.is_empty());
+
+    // The config files themselves are never renamed.
+    std::fs::write(show.join("a.txt"), "").unwrap();
+    let files = renamer::plan::collect_files(&show, 1, &[]);
+    assert_eq!(files, vec![show.join("a.txt")]);
+}